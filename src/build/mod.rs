@@ -0,0 +1,319 @@
+use std::{path::{Path, PathBuf}, sync::Arc, time::Instant};
+
+use tokio::{fs::{create_dir_all, remove_dir_all}, task::JoinHandle};
+
+use crate::{check::{check_site, Report}, convert::{dir::unpage_children, feed::render_feeds, file::FileType, highlight::HIGHLIGHT_CSS, prepare_node, render_node, search::render_search_index, sitemap::render_sitemap, tag::render_tags}, error::HeliumError, index::{backlink::{collect_backlink, BacklinkMap}, build_ignore_set, build_tree, collect_resource, find_parent, flatten_dir_node, flatten_file_node, flatten_node, init_remaining_path, Node, NodePath, NodeType, ResourceMap}, site::Site, util::fs::{copy_recursive, publish_staging, reset_staging, staging_path, write_from_slice, write_from_string}};
+
+/*
+ * result of a full build, kept alive by watch to apply incremental changes
+ */
+pub struct Build {
+    pub root: Arc<Node>,
+    pub resource_map: Arc<ResourceMap>,
+    pub backlink_map: Arc<BacklinkMap>,
+    pub report: Report,
+    /*
+     * directory the nodes are rendered into, the staging directory until the build is published
+     */
+    pub target: PathBuf,
+    /*
+     * nodes left out of the site by `--keep-going`
+     */
+    pub failures: Vec<HeliumError>,
+}
+
+pub fn collect_documents_path(collect_documents: bool) -> Option<&'static Path> {
+    match collect_documents {
+        true => Some(Path::new("post")),
+        false => None,
+    }
+}
+
+/*
+ * parse the vault and resolve paths and links, everything a build does before rendering
+ */
+pub async fn load_site(site: &Site, target: &Path) -> Result<Build, HeliumError> {
+    let context = &site.context;
+
+    /*
+     * build tree
+     */
+    let root = Arc::new(Node::default(NodePath { org_path: "/".into(), rel_path: "./".into(), abs_path: Some("/".into()), target_path: None }).await);
+    let ignore = build_ignore_set(&context.ignore)?;
+    let mut ignored = Vec::new();
+    let mut children = root.children.write().await;
+    for node in &context.nodes {
+        let (tree, mut ignored_files) = build_tree(node, &ignore).await?;
+        children.push(tree);
+        ignored.append(&mut ignored_files);
+
+    }
+    drop(children);
+    // println!("build tree: {:?}", start_time.elapsed());
+
+    /*
+     * prepare data by parsing each node
+     */
+    let nodes = flatten_node(&root).await;
+    let mut handles = vec![];
+    for node in &nodes {
+        let node = node.clone();
+        let site = site.clone();
+        let handle: JoinHandle<Result<(), HeliumError>> = tokio::spawn(async move {
+            prepare_node(&site, &node).await?;
+            Ok(())
+        });
+        handles.push(handle);
+    }
+    let mut failures = Vec::new();
+    for handle in handles {
+        /*
+         * a node that failed to prepare is already detached by sort_children of its parent
+         */
+        if let Err(err) = handle.await? {
+            match context.keep_going {
+                true => failures.push(err),
+                false => return Err(err),
+            }
+        }
+    }
+    // println!("prepare node: {:?}", start_time.elapsed());
+
+
+    /*
+     * init target_path
+     */
+    let collect_documents = collect_documents_path(context.render.collect_documents);
+    init_remaining_path(&root, target, &collect_documents).await?;
+    // index::print_tree(root.clone(), 0).await;
+    // println!("init target path: {:?}", start_time.elapsed());
+
+
+    /*
+     * collect resources for rendering
+     */
+    let mut resource_map = collect_resource(&root).await?;
+    for node in &nodes {
+        if let NodeType::File(lk) = &node.property.node_type {
+            if let Some(FileType::Markdown(fp, doc)) = &*lk.read().await {
+                if doc.is_excluded(context) {
+                    resource_map.exclude(fp.key.clone());
+                }
+            }
+        }
+    }
+    for (key, path) in ignored {
+        resource_map.ignore(key, path);
+    }
+    let resource_map = Arc::new(resource_map);
+    let backlink_map = Arc::new(collect_backlink(&resource_map).await?);
+
+    let mut build = Build {
+        root,
+        resource_map,
+        backlink_map,
+        report: Report::default(),
+        target: target.to_path_buf(),
+        failures,
+    };
+    build.report = check_site(&build).await?;
+
+    Ok(build)
+}
+
+/*
+ * build the whole site into a staging directory and publish it to target_base when every step succeeded
+ */
+pub async fn build_site(site: &Site) -> Result<Build, HeliumError> {
+    let start_time = Instant::now();
+    let context = &site.context;
+
+    let staging = staging_path(&context.target_base)?;
+    reset_staging(&staging).await?;
+    let mut build = match render_site(site, &staging).await {
+        Ok(build) => build,
+        Err(err) => {
+            /*
+             * target_base keeps the previous site
+             */
+            let _ = remove_dir_all(&staging).await;
+            return Err(err);
+        },
+    };
+    publish_staging(&staging, &context.target_base).await?;
+
+    /*
+     * incremental rebuilds write into target_base directly
+     */
+    let collect_documents = collect_documents_path(context.render.collect_documents);
+    init_remaining_path(&build.root, &context.target_base, &collect_documents).await?;
+    build.target = context.target_base.clone();
+
+    println!("total elapsed: {:?}", start_time.elapsed());
+    build.report.print_summary();
+    print_failures(&build.failures);
+
+    Ok(build)
+}
+
+/*
+ * render every page, feed and index of the site into target
+ */
+async fn render_site(site: &Site, target: &Path) -> Result<Build, HeliumError> {
+    let context = &site.context;
+    let mut build = load_site(site, target).await?;
+    let Build { root, resource_map, backlink_map, .. } = &build;
+    let collect_documents = collect_documents_path(context.render.collect_documents);
+    // println!("load site: {:?}", start_time.elapsed());
+
+    /*
+     * copy static files
+     */
+    let mut static_dir = target.to_path_buf();
+    static_dir.push("static");
+    for path in &context.render.r#static {
+        copy_recursive(&site.file_limit, path, &static_dir, false).await?;
+    }
+    if let Some(highlighter) = &site.highlighter {
+        create_dir_all(&static_dir).await?;
+        write_from_string(&site.file_limit, &static_dir.join(HIGHLIGHT_CSS), highlighter.css().to_string()).await?;
+    }
+    // println!("collect resource & remove and copy files: {:?}", start_time.elapsed());
+
+    /*
+     * create directories
+     */
+    let dir_nodes = flatten_dir_node(root).await;
+    let mut handles = vec![];
+    for node in &dir_nodes {
+        let node = node.clone();
+        let handle: JoinHandle<Result<(), HeliumError>> = tokio::spawn(async move {
+            let path_lk = node.path.read().await;
+            match &path_lk.target_path {
+                Some(target_path) => {
+                    create_dir_all(target_path).await.map_err(|err| HeliumError::from(err).at(target_path))?;
+                },
+                None => {
+                    return Err(HeliumError::from("target_path not ready").at(&path_lk.org_path));
+                }
+            }
+            Ok(())
+        });
+        handles.push(handle);
+    }
+    for handle in handles {
+        handle.await??;
+    }
+    if let Some(path) = collect_documents {
+        create_dir_all(target.join(path)).await?;
+    }
+    // println!("create directories: {:?}", start_time.elapsed());
+    
+
+    /*
+     * render files before directories, a listing waits for the images of its documents
+     * and a document that failed never provides them
+     */
+    let mut failures = render_nodes(site, &flatten_file_node(root).await, resource_map, backlink_map).await?;
+    for (node, _) in &failures {
+        detach_node(site, root, node).await?;
+    }
+    if !failures.is_empty() {
+        /*
+         * directories that lost a document are paged again, new page nodes need paths and directories
+         */
+        init_remaining_path(root, target, &collect_documents).await?;
+        for node in flatten_dir_node(root).await {
+            if let Some(target_path) = &node.path.read().await.target_path {
+                create_dir_all(target_path).await?;
+            }
+        }
+    }
+    failures.append(&mut render_nodes(site, &flatten_dir_node(root).await, resource_map, backlink_map).await?);
+    // println!("render node: {:?}", start_time.elapsed());
+
+    /*
+     * render search indices
+     */
+    write_search_index(site, root, target).await?;
+
+    /*
+     * render tag pages
+     */
+    render_tags(site, &build).await?;
+
+    /*
+     * render feeds and sitemap
+     */
+    render_feeds(site, &build).await?;
+    render_sitemap(site, &build).await?;
+
+    build.failures.extend(failures.into_iter().map(|(_, err)| err));
+
+    Ok(build)
+
+}
+
+/*
+ * render nodes concurrently, with `--keep-going` failures are returned with their node instead of stopping
+ */
+async fn render_nodes(site: &Site, nodes: &[Arc<Node>], resource_map: &Arc<ResourceMap>, backlink_map: &Arc<BacklinkMap>) -> Result<Vec<(Arc<Node>, HeliumError)>, HeliumError> {
+    let mut handles = vec![];
+    for node in nodes {
+        let node = node.clone();
+        let site = site.clone();
+        let resource_map = resource_map.clone();
+        let backlink_map = backlink_map.clone();
+        let handle: JoinHandle<Result<(), HeliumError>> = tokio::spawn(async move {
+            render_node(&site, &node, resource_map, backlink_map).await?;
+            Ok(())
+        });
+        handles.push(handle);
+    }
+
+    let mut failures = Vec::new();
+    for (node, handle) in nodes.iter().zip(handles) {
+        if let Err(err) = handle.await? {
+            match site.context.keep_going {
+                true => failures.push((node.clone(), err)),
+                false => return Err(err),
+            }
+        }
+    }
+    Ok(failures)
+}
+
+/*
+ * take a document that failed to render out of its directory, which is sorted and paged again without it
+ */
+async fn detach_node(site: &Site, root: &Arc<Node>, node: &Arc<Node>) -> Result<(), HeliumError> {
+    let parent = find_parent(root, node).await.ok_or("parent of failed node not found")?;
+    unpage_children(&parent).await?;
+    parent.children.write().await.retain(|child| !Arc::ptr_eq(child, node));
+    prepare_node(site, &parent).await
+}
+
+/*
+ * every node left out by `--keep-going`, printed at the end of a build
+ */
+pub fn print_failures(failures: &[HeliumError]) {
+    if failures.is_empty() {
+        return;
+    }
+    for err in failures {
+        eprintln!("{}", err);
+    }
+    eprintln!("build: {} node(s) failed and were left out of the site", failures.len());
+}
+
+pub async fn write_search_index(site: &Site, root: &Arc<Node>, target: &Path) -> Result<(), HeliumError> {
+    let indices = render_search_index(site, root.clone()).await?;
+    if let Some(path) = collect_documents_path(site.context.render.collect_documents) {
+        /*
+         * export search index to post directory
+         */
+        let binary = bincode::serialize(&indices)?;
+        write_from_slice(&site.file_limit, &target.join(path).join("searchindex"), &binary[..]).await?;
+    }
+    Ok(())
+}
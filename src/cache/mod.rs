@@ -0,0 +1,91 @@
+use std::{collections::HashSet, path::{Path, PathBuf}};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::fs::{create_dir_all, read, write};
+
+use crate::{convert::file::{DocumentLinkType, DocumentProperty, TocEntry}, error::HeliumError, site::Site};
+
+/*
+ * entries written by another helium version are ignored
+ */
+pub static CACHE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/*
+ * entries written in another format are ignored, bump it in the same change when
+ * - a field of CacheEntry, DocumentProperty, DocumentLinkType, RenderedHtml or TocEntry changes
+ * - parsing stores a different raw, token, summary, link or image for the same note
+ * - rendering stores different html or toc for the same note
+ */
+pub static CACHE_FORMAT: u32 = 14;
+
+/*
+ * parsed document, keyed by source path and content hash
+ */
+#[derive(Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub version: String,
+    pub format: u32,
+    pub tokenizer: String,
+    pub path: PathBuf,
+    pub hash: String,
+    pub raw: String,
+    pub line_offset: usize,
+    pub property: DocumentProperty,
+    pub token: HashSet<String>,
+    pub summary: String,
+    pub link: Vec<(usize, usize, DocumentLinkType)>,
+    pub image: Vec<String>,
+    pub html: Option<RenderedHtml>,
+}
+
+/*
+ * html is only valid for the markdown it was converted from after links were resolved
+ */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RenderedHtml {
+    pub source_hash: String,
+    pub html: String,
+    pub toc: Vec<TocEntry>,
+}
+
+pub fn content_hash(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+fn entry_path(site: &Site, path: &Path) -> Option<PathBuf> {
+    let cache_dir = site.context.cache_dir.as_ref()?;
+    Some(cache_dir.join(format!("{}.bin", content_hash(path.as_os_str().as_encoded_bytes()))))
+}
+
+/*
+ * any unreadable or outdated entry is a cache miss
+ */
+pub async fn load(site: &Site, path: &Path, hash: &str) -> Option<CacheEntry> {
+    let binary = read(entry_path(site, path)?).await.ok()?;
+    let entry: CacheEntry = bincode::deserialize(&binary).ok()?;
+    match entry.version == CACHE_VERSION && entry.format == CACHE_FORMAT && entry.tokenizer == site.tokenizer.id() && entry.path == path && entry.hash == hash {
+        true => Some(entry),
+        false => None,
+    }
+}
+
+pub async fn store(site: &Site, entry: CacheEntry) -> Result<(), HeliumError> {
+    let target = match entry_path(site, &entry.path) {
+        Some(target) => target,
+        None => return Ok(()),
+    };
+    if let Some(parent) = target.parent() {
+        create_dir_all(parent).await?;
+    }
+    write(target, bincode::serialize(&entry)?).await?;
+    Ok(())
+}
+
+pub async fn store_html(site: &Site, path: &Path, hash: &str, html: &RenderedHtml) -> Result<(), HeliumError> {
+    if let Some(mut entry) = load(site, path, hash).await {
+        entry.html = Some(html.clone());
+        store(site, entry).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,257 @@
+use std::{collections::HashSet, path::{Path, PathBuf}};
+
+use crate::{build::{load_site, Build}, context::{Context, Strictness}, convert::{file::{DocumentLinkType, FileType}, highlight::Highlighter, render::Template}, error::{HeliumError, Position}, index::{flatten_file_node, NodeType}, site::Site, util::token::create_tokenizer};
+
+/*
+ * wikilink or image whose target is not in the resource map
+ */
+#[derive(Debug)]
+pub struct Unresolved {
+    pub path: PathBuf,
+    pub line: usize,
+    pub column: usize,
+    pub link: String,
+    pub image: bool,
+    /*
+     * file the link would reach if it was not ignored
+     */
+    pub ignored: Option<PathBuf>,
+}
+
+/*
+ * link matching files in several folders, resolved to the one closest to the linking note
+ */
+#[derive(Debug)]
+pub struct Ambiguous {
+    pub path: PathBuf,
+    pub line: usize,
+    pub column: usize,
+    pub link: String,
+    pub resolved: PathBuf,
+    pub candidates: usize,
+}
+
+/*
+ * unresolved links and duplicate keys are errors, everything else is a warning
+ */
+#[derive(Debug, Default)]
+pub struct Report {
+    pub unresolved: Vec<Unresolved>,
+    pub ambiguous: Vec<Ambiguous>,
+    /*
+     * key shared by several files, with the source and served path of each
+     */
+    pub duplicates: Vec<(String, Vec<(PathBuf, PathBuf)>)>,
+    pub orphans: Vec<PathBuf>,
+    pub unused: Vec<PathBuf>,
+}
+impl Report {
+    pub fn errors(&self) -> usize {
+        self.unresolved.len() + self.duplicates.len()
+    }
+    pub fn warnings(&self) -> usize {
+        self.ambiguous.len() + self.orphans.len() + self.unused.len()
+    }
+    pub fn exceeds(&self, strictness: Strictness) -> bool {
+        match strictness {
+            Strictness::None => false,
+            Strictness::Error => self.errors() > 0,
+            Strictness::Warning => self.errors() + self.warnings() > 0,
+        }
+    }
+
+    fn print_errors(&self) {
+        for unresolved in &self.unresolved {
+            let kind = match unresolved.image {
+                true => "image",
+                false => "link",
+            };
+            eprintln!("error: unresolved {} {}", kind, unresolved.link);
+            eprintln!("  --> {}:{}:{}", unresolved.path.display(), unresolved.line, unresolved.column);
+            if let Some(ignored) = &unresolved.ignored {
+                eprintln!("  = note: {} is ignored", ignored.display());
+            }
+        }
+        for (key, paths) in &self.duplicates {
+            eprintln!("error: duplicate key \"{}\", links without a folder resolve to the closest file", key);
+            for (org_path, abs_path) in paths {
+                eprintln!("  --> {} (served at {})", org_path.display(), abs_path.display());
+            }
+        }
+    }
+    fn print_warnings(&self) {
+        for ambiguous in &self.ambiguous {
+            eprintln!("warning: ambiguous link {} matches {} files, resolved to {}", ambiguous.link, ambiguous.candidates, ambiguous.resolved.display());
+            eprintln!("  --> {}:{}:{}", ambiguous.path.display(), ambiguous.line, ambiguous.column);
+        }
+        for path in &self.orphans {
+            eprintln!("warning: orphan note, nothing links to it");
+            eprintln!("  --> {}", path.display());
+        }
+        for path in &self.unused {
+            eprintln!("warning: unused file, nothing references it");
+            eprintln!("  --> {}", path.display());
+        }
+    }
+
+    /*
+     * every finding, printed by the check subcommand
+     */
+    pub fn print(&self) {
+        self.print_errors();
+        self.print_warnings();
+        eprintln!("check: {} error(s), {} warning(s)", self.errors(), self.warnings());
+    }
+
+    /*
+     * errors and counts, printed at the end of a build
+     */
+    pub fn print_summary(&self) {
+        self.print_errors();
+        match self.warnings() {
+            0 => eprintln!("check: {} error(s), 0 warning(s)", self.errors()),
+            n => eprintln!("check: {} error(s), {} warning(s), run `helium check` for details", self.errors(), n),
+        }
+    }
+}
+
+/*
+ * file name a markdown image url points to
+ */
+fn url_key(url: &str) -> String {
+    let url = url.split(['?', '#']).next().unwrap_or(url);
+    url.rsplit('/').next().unwrap_or(url).replace("%20", " ")
+}
+
+pub async fn check_site(build: &Build) -> Result<Report, HeliumError> {
+    let mut report = Report::default();
+    let mut referenced = HashSet::new();
+    let mut binaries = Vec::new();
+
+    for node in flatten_file_node(&build.root).await {
+        let org_path = node.path.read().await.org_path.clone();
+        let lk = match &node.property.node_type {
+            NodeType::File(lk) => lk.read().await,
+            NodeType::Dir(_) => continue,
+        };
+        match &*lk {
+            Some(FileType::Markdown(_, doc)) => {
+                if !build.backlink_map.contains_key(&org_path) {
+                    report.orphans.push(org_path.clone());
+                }
+
+                for (start, end, link_type) in &doc.parameter.link {
+                    let link = link_type.link();
+                    if link.target.is_empty() {
+                        continue;
+                    }
+                    let Position { line, column } = doc.position(*start);
+                    let candidates = build.resource_map.candidates(link);
+                    match build.resource_map.resolve(link, &doc.path) {
+                        Some(resolved) => {
+                            referenced.insert(resolved.org_path.clone());
+                            if candidates.len() > 1 {
+                                report.ambiguous.push(Ambiguous {
+                                    path: org_path.clone(),
+                                    line,
+                                    column,
+                                    link: doc.raw[*start..*end].to_string(),
+                                    resolved: resolved.org_path.clone(),
+                                    candidates: candidates.len(),
+                                });
+                            }
+                        },
+                        None if build.resource_map.is_excluded(link) => {},
+                        None => {
+                            report.unresolved.push(Unresolved {
+                                path: org_path.clone(),
+                                line,
+                                column,
+                                link: doc.raw[*start..*end].to_string(),
+                                image: matches!(link_type, DocumentLinkType::Image(_)),
+                                ignored: build.resource_map.ignored(link).map(PathBuf::from),
+                            });
+                        },
+                    }
+                }
+                for url in &*doc.parameter.image.read().await {
+                    for resource in build.resource_map.get(&url_key(url)) {
+                        referenced.insert(resource.org_path.clone());
+                    }
+                }
+            },
+            Some(FileType::Binary(_)) => {
+                binaries.push(org_path);
+            },
+            None => return Err("File not ready".into()),
+        }
+    }
+
+    for path in binaries {
+        if !referenced.contains(&path) {
+            report.unused.push(path);
+        }
+    }
+    report.duplicates = build.resource_map.duplicates()
+        .map(|(key, resources)| {
+            let paths = resources.iter()
+                .map(|resource| (resource.org_path.clone(), resource.abs_path.clone()))
+                .collect();
+            (key.clone(), paths)
+        })
+        .collect();
+    report.duplicates.sort();
+    report.unused.sort();
+    report.orphans.sort();
+
+    Ok(report)
+}
+
+/*
+ * `helium check`, loads the vault without rendering and prints every finding
+ */
+pub async fn check(site: &Site, strictness: Option<Strictness>) -> Result<(), HeliumError> {
+    let build = load_site(site, &site.context.target_base).await?;
+    build.report.print();
+    enforce(site, &build.report, strictness)
+}
+
+/*
+ * `helium config check`, loads the config, templates, highlighter and tokenizer without reading the vault
+ * and prints every problem instead of stopping at the first one
+ */
+pub async fn check_config(path: &Path) -> Result<(), HeliumError> {
+    let context = Context::new(path)?;
+    let mut errors = context.validate();
+    if let Err(err) = Template::new(&context) {
+        errors.push(err);
+    }
+    if let Err(err) = Highlighter::new(&context) {
+        errors.push(err);
+    }
+    if let Err(err) = create_tokenizer(&context.tokenizer).await {
+        errors.push(err);
+    }
+
+    for err in &errors {
+        eprintln!("{}", err);
+    }
+    match errors.len() {
+        0 => {
+            println!("config: {} is valid", path.display());
+            Ok(())
+        },
+        count => Err(format!("config check found {} problem(s)", count).into()),
+    }
+}
+
+/*
+ * fail when the findings exceed the given strictness, or the configured one
+ */
+pub fn enforce(site: &Site, report: &Report, strictness: Option<Strictness>) -> Result<(), HeliumError> {
+    let strictness = strictness.unwrap_or(site.context.check.strictness);
+    match report.exceeds(strictness) {
+        true => Err(format!("check failed with {} error(s) and {} warning(s)", report.errors(), report.warnings()).into()),
+        false => Ok(()),
+    }
+}
@@ -1,36 +1,379 @@
-use std::path::{Path, PathBuf};
+use std::{ops::RangeInclusive, path::{Path, PathBuf}};
 
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
 use serde::Deserialize;
 
+use crate::{error::{HeliumError, Position}, util::token::STOP_WORD_LANGUAGES};
+
 #[derive(Parser, Debug)]
 pub struct Args {
-    #[arg(short, long)]
+    #[arg(short, long, global = true, default_value = "config.yaml")]
     pub config: PathBuf, 
+    /// Include documents marked `draft: true` or `publish: false`
+    #[arg(long, global = true)]
+    pub drafts: bool,
+    /// Leave out documents that fail to build instead of stopping, and list the failures at the end
+    #[arg(long, global = true)]
+    pub keep_going: bool,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Build the site and serve it on localhost, rebuilding on every change
+    Serve {
+        #[arg(short, long, default_value_t = 8080)]
+        port: u16,
+        /// Build into this directory instead of a temporary one
+        #[arg(short, long)]
+        target: Option<PathBuf>,
+    },
+    /// Build the site, then rebuild only the nodes affected by each vault change
+    Watch,
+    /// Report unresolved links, duplicate keys, orphan notes and unused files without rendering
+    Check {
+        /// Overrides `check.strictness` of the config file
+        #[arg(short, long, value_enum)]
+        strictness: Option<Strictness>,
+    },
+    /// Inspect the config file
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Report every problem of the config file, its paths and templates without building
+    Check,
 }
 
 #[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
 pub struct Context {
     pub nodes: Vec<PathBuf>,
     pub target_base: PathBuf,
+    /*
+     * absolute url the site is deployed at, feeds are only written when it is set
+     */
+    pub base_url: Option<String>,
     pub open_file_limit: usize,
-    pub render: RenderContext
+    /*
+     * parsed documents are cached here, defaults to .helium-cache next to the config file
+     */
+    pub cache_dir: Option<PathBuf>,
+    pub render: RenderContext,
+    #[serde(default)]
+    pub tokenizer: TokenizerContext,
+    #[serde(default)]
+    pub check: CheckContext,
+    /*
+     * glob patterns of files and directories left out of the site, relative to each node
+     */
+    #[serde(default)]
+    pub ignore: Vec<String>,
+    /*
+     * set by `serve`, injects the live reload script into rendered pages
+     */
+    #[serde(skip)]
+    pub live_reload: bool,
+    /*
+     * set by `--drafts`, drafts are built like any other document
+     */
+    #[serde(skip)]
+    pub drafts: bool,
+    /*
+     * set by `--keep-going`, failed nodes are left out of the site instead of stopping the build
+     */
+    #[serde(skip)]
+    pub keep_going: bool,
+    /*
+     * file the context was loaded from, problems found after parsing point into it
+     */
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
 }
 
 #[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
 pub struct RenderContext {
     pub template: String,
     pub profile: Option<PathBuf>,
     pub collect_documents: bool,
     pub r#static: Vec<PathBuf>,
     pub list_size: usize,
+    #[serde(default)]
+    pub feed: FeedContext,
+    /*
+     * code blocks are highlighted at build time when set, instead of by highlight.js in the browser
+     */
+    pub highlight: Option<HighlightContext>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct HighlightContext {
+    /*
+     * one of the themes bundled with syntect
+     */
+    #[serde(default = "default_theme")]
+    pub theme: String,
+    /*
+     * used instead of theme in dark mode
+     */
+    pub dark_theme: Option<String>,
+    #[serde(default = "default_line_numbers")]
+    pub line_numbers: bool,
+}
+fn default_theme() -> String {
+    "InspiredGitHub".into()
+}
+fn default_line_numbers() -> bool {
+    true
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct FeedContext {
+    /*
+     * number of the latest documents in each feed
+     */
+    #[serde(default = "default_feed_entries")]
+    pub entries: usize,
+    /*
+     * rendered html of the documents instead of their summary
+     */
+    #[serde(default)]
+    pub full_content: bool,
+    /*
+     * feeds of each top-level directory, next to the feeds of the whole site
+     */
+    #[serde(default)]
+    pub per_directory: bool,
+}
+impl Default for FeedContext {
+    fn default() -> Self {
+        FeedContext {
+            entries: default_feed_entries(),
+            full_content: false,
+            per_directory: false,
+        }
+    }
+}
+fn default_feed_entries() -> usize {
+    20
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct TokenizerContext {
+    #[serde(default)]
+    pub kind: TokenizerKind,
+    /*
+     * iso codes of the stop word lists, see the stop-words crate
+     */
+    #[serde(default = "default_stop_words")]
+    pub stop_words: Vec<String>,
+    /*
+     * number of python processes, kiwi only
+     */
+    #[serde(default = "default_workers")]
+    pub workers: usize,
+}
+impl Default for TokenizerContext {
+    fn default() -> Self {
+        TokenizerContext {
+            kind: TokenizerKind::default(),
+            stop_words: default_stop_words(),
+            workers: default_workers(),
+        }
+    }
+}
+fn default_stop_words() -> Vec<String> {
+    vec!["ko".into(), "en".into()]
+}
+fn default_workers() -> usize {
+    5
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenizerKind {
+    #[default]
+    Builtin,
+    Kiwi,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct CheckContext {
+    /*
+     * findings above this level make build and check exit with an error
+     */
+    #[serde(default)]
+    pub strictness: Strictness,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Strictness {
+    /// Only report findings
+    #[default]
+    None,
+    /// Fail on unresolved links and duplicate keys
+    Error,
+    /// Fail on ambiguous links, orphan notes and unused files as well
+    Warning,
 }
 
 impl Context {
-    pub fn new(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
-        let yaml = std::fs::read_to_string(path)?;
-        let context: Context = serde_yaml::from_str(&yaml)?;         
+    pub fn new(path: &Path) -> Result<Self, HeliumError> {
+        let yaml = std::fs::read_to_string(path).map_err(|err| HeliumError::from(err).at(path))?;
+        let mut context: Context = serde_yaml::from_str(&yaml).map_err(|err| {
+            HeliumError::Config { path: Some(path.to_path_buf()), position: HeliumError::yaml_position(&err, 0), message: suggest_field(&err.to_string()) }
+        })?;
+        if context.cache_dir.is_none() {
+            let config_dir = path.parent().unwrap_or(Path::new("."));
+            context.cache_dir = Some(config_dir.join(".helium-cache"));
+        }
+        context.path = Some(path.to_path_buf());
         
         Ok(context)
     }
+
+    /*
+     * problems serde cannot see, every one of them is returned so `config check` lists them at once
+     */
+    pub fn validate(&self) -> Vec<HeliumError> {
+        let mut errors = Vec::new();
+
+        if self.nodes.is_empty() {
+            errors.push(self.error_at("nodes:", "no nodes given, the site would be empty"));
+        }
+        for node in &self.nodes {
+            if !node.exists() {
+                errors.push(self.error_at(&node.to_string_lossy(), format!("node {} does not exist", node.display())));
+            }
+        }
+
+        if let Err(message) = check_writable(&self.target_base) {
+            errors.push(self.error_at("target_base:", message));
+        }
+
+        if !OPEN_FILE_LIMIT.contains(&self.open_file_limit) {
+            errors.push(self.error_at("open_file_limit:", format!(
+                "open_file_limit {} is out of range, expected {} to {}",
+                self.open_file_limit, OPEN_FILE_LIMIT.start(), OPEN_FILE_LIMIT.end(),
+            )));
+        }
+
+        if self.render.list_size == 0 {
+            errors.push(self.error_at("list_size:", "list_size should be at least 1"));
+        }
+        if let Some(profile) = &self.render.profile {
+            if !profile.is_file() {
+                errors.push(self.error_at("profile:", format!("profile {} does not exist", profile.display())));
+            }
+        }
+        for path in &self.render.r#static {
+            if !path.exists() {
+                errors.push(self.error_at(&path.to_string_lossy(), format!("static path {} does not exist", path.display())));
+            }
+        }
+
+        for language in &self.tokenizer.stop_words {
+            if !STOP_WORD_LANGUAGES.contains(&language.as_str()) {
+                errors.push(self.error_at("stop_words:", format!("unknown stop word list `{}`, expected an iso code like `en` or `ko`", language)));
+            }
+        }
+
+        errors
+    }
+
+    /*
+     * a config error pointing at the first line of the config file that mentions needle
+     */
+    pub fn error_at(&self, needle: &str, message: impl Into<String>) -> HeliumError {
+        let position = self.path.as_ref()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|yaml| {
+                yaml.lines().enumerate().find_map(|(idx, line)| {
+                    line.find(needle).map(|byte| Position { line: idx + 1, column: line[..byte].chars().count() + 1 })
+                })
+            });
+        HeliumError::Config { path: self.path.clone(), position, message: message.into() }
+    }
+}
+
+/*
+ * below 20 nothing is left for rendering, see util::fs::FileLimit
+ */
+static OPEN_FILE_LIMIT: RangeInclusive<usize> = 32..=65536;
+
+/*
+ * target_base and the staging directory next to it are created by the build, so the closest existing
+ * ancestor is probed when target_base does not exist yet
+ */
+fn check_writable(target: &Path) -> Result<(), String> {
+    if target.exists() && !target.is_dir() {
+        return Err(format!("target_base {} is not a directory", target.display()));
+    }
+    let parent = match target.parent() {
+        Some(parent) if parent.as_os_str().is_empty() => Path::new("."),
+        Some(parent) => parent,
+        None => return Err("target_base cannot be the root directory".into()),
+    };
+    let probe_dir = parent.ancestors().find(|dir| dir.exists()).unwrap_or(Path::new("."));
+    for dir in [probe_dir, target] {
+        if !dir.is_dir() {
+            continue;
+        }
+        let probe = dir.join(format!(".helium-write-check-{}", std::process::id()));
+        match std::fs::write(&probe, b"") {
+            Ok(()) => {
+                let _ = std::fs::remove_file(&probe);
+            },
+            Err(err) => return Err(format!("target_base cannot be written, {} is not writable ({})", dir.display(), err)),
+        }
+    }
+    Ok(())
+}
+
+/*
+ * serde lists the known fields when it meets an unknown one, the closest of them is suggested instead
+ */
+fn suggest_field(message: &str) -> String {
+    if !message.contains("unknown field") {
+        return message.to_string();
+    }
+    let mut names = message.split('`').skip(1).step_by(2);
+    let unknown = match names.next() {
+        Some(unknown) => unknown,
+        None => return message.to_string(),
+    };
+    let suggestion = names
+        .map(|name| (edit_distance(unknown, name), name))
+        .filter(|(distance, name)| *distance <= (name.len() / 3).max(2))
+        .min();
+    match suggestion {
+        Some((_, name)) => format!("unknown field `{}`, did you mean `{}`?", unknown, name),
+        None => message.to_string(),
+    }
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
 }
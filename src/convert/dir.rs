@@ -3,7 +3,7 @@ use std::{ops::Neg, path::PathBuf, str::FromStr, sync::Arc};
 
 use tokio::sync::RwLock;
 
-use crate::{get_context, index::{Node, NodePath, NodeProperty, NodeType}, util::{resolve_osstr_default, resolve_path, sem::Lock}};
+use crate::{context::Context, error::HeliumError, index::{Node, NodePath, NodeProperty, NodeType}, util::{resolve_osstr_default, resolve_path, sem::Lock}};
 
 use super::{render::{List, Page, Prop}, FileType};
 
@@ -13,11 +13,11 @@ pub enum DirType {
     Page(usize, usize), // (index, total)
 }
 impl DirType {
-    pub async fn new(node: &Arc<Node>) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+    pub async fn new(context: &Context, node: &Arc<Node>) -> Result<Self, HeliumError> {
         /*
          * !sort_children wait until child node is ready
          */
-        sort_children(node).await?;
+        sort_children(context, node).await?;
         
         /*
          * !page_children aquire write lock of (node.path, node.children)
@@ -27,7 +27,7 @@ impl DirType {
         let lk = node.children.read().await;
         let child_node_size = count_valid_children(&lk).await?;
         drop(lk);
-        let is_paged = page_children(node, get_context().await.render.list_size).await?;
+        let is_paged = page_children(node, context.render.list_size).await?;
 
         /* for child in &*lk {
             println!("{:?}", child.property.node_type);
@@ -50,7 +50,7 @@ pub struct DirProperty {
     pub is_paged: bool,
 }
 
-pub async fn count_valid_children(children: &Vec<Arc<Node>>) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+pub async fn count_valid_children(children: &Vec<Arc<Node>>) -> Result<usize, HeliumError> {
     let mut count = 0;
     for child in children {
         match &child.property.node_type {
@@ -81,7 +81,7 @@ pub async fn count_valid_children(children: &Vec<Arc<Node>>) -> Result<usize, Bo
     Ok(count)
 }
 
-pub async fn sort_children(node: &Arc<Node>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+pub async fn sort_children(context: &Context, node: &Arc<Node>) -> Result<(), HeliumError> {
     let mut children = node.children.write().await;
 
     /*
@@ -104,22 +104,31 @@ pub async fn sort_children(node: &Arc<Node>) -> Result<(), Box<dyn std::error::E
                          */
                         sorted_children.push(((0, 0, 0, dp.key.clone()), child));
                     },
-                    _ => {
-                        return Err("locking panic!".into()); 
-                    }
+                    /*
+                     * the child failed to prepare, its own task reports why
+                     */
+                    _ => continue,
                 }
             },
             NodeType::File(lk) => {
                 let lk = lk.read().await;
                 match &*lk {
                     Some(FileType::Markdown(fp, doc)) => {
+                        /*
+                         * drafts are detached here, so they are neither listed, rendered nor indexed
+                         */
+                        if doc.is_excluded(context) {
+                            continue;
+                        }
                         let priority = match doc.property.priority {
                             Some(p) => p as i32,
                             None => 0,
                         };
                         let created_at = match &doc.property.created_at {
                             Some(time) => {
-                                let dt = chrono::NaiveDateTime::parse_from_str(&format!("{} 00:00:00", &time), "%Y-%m-%d %H:%M:%S")?.and_utc();
+                                let dt = chrono::NaiveDateTime::parse_from_str(&format!("{} 00:00:00", &time), "%Y-%m-%d %H:%M:%S")
+                                    .map_err(|err| HeliumError::front_matter(format!("invalid created_at \"{}\", expected YYYY-MM-DD ({})", time, err), None).at(&doc.path))?
+                                    .and_utc();
                                 dt.timestamp().neg()
                             },
                             None => {0},
@@ -129,7 +138,7 @@ pub async fn sort_children(node: &Arc<Node>) -> Result<(), Box<dyn std::error::E
                     Some(FileType::Binary(fp)) => {
                         sorted_children.push(((0, 1, 0, fp.key.clone()), child));
                     },
-                    _ => return Err("locking panic!".into())
+                    _ => continue,
                 }
             }
         }
@@ -145,7 +154,7 @@ pub async fn sort_children(node: &Arc<Node>) -> Result<(), Box<dyn std::error::E
     Ok(())
 }
 
-pub async fn page_children(node: &Arc<Node>, page_size: usize) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+pub async fn page_children(node: &Arc<Node>, page_size: usize) -> Result<bool, HeliumError> {
     let mut children_lk = node.children.write().await;
     let mut children_left = Vec::new();
     let mut children_target = Vec::new();
@@ -203,7 +212,36 @@ pub async fn page_children(node: &Arc<Node>, page_size: usize) -> Result<bool, B
     Ok(true)
 }
 
-pub async fn convert_render_list(children: &Vec<Arc<Node>>) -> Result<Vec<List>, Box<dyn std::error::Error + Send + Sync>> {
+/*
+ * undo page_children so the directory can be sorted and paged again
+ */
+pub async fn unpage_children(node: &Arc<Node>) -> Result<(), HeliumError> {
+    let mut children_lk = node.children.write().await;
+    let mut children = Vec::new();
+    for child in &*children_lk {
+        match &child.property.node_type {
+            NodeType::Dir(lk) => {
+                match &*lk.read().await {
+                    Some(DirType::Page(_, _)) => {
+                        children.extend(child.children.read().await.iter().cloned());
+                    },
+                    _ => {
+                        children.push(child.clone());
+                    }
+                }
+            },
+            _ => {
+                children.push(child.clone());
+            }
+        }
+    }
+    *children_lk = children;
+
+    Ok(())
+}
+
+#[allow(clippy::single_match)]
+pub async fn convert_render_list(children: &Vec<Arc<Node>>) -> Result<Vec<List>, HeliumError> {
     let mut list = Vec::new();
     for child in children {
         // wait for all children to be ready
@@ -277,7 +315,8 @@ pub async fn convert_render_list(children: &Vec<Arc<Node>>) -> Result<Vec<List>,
     Ok(list)
 }
 
-pub async fn convert_render_page(rel: PathBuf, page: Option<(usize, usize)>) -> Result<(Vec<Page>, Prop), Box<dyn std::error::Error + Send + Sync>> {
+#[allow(clippy::single_match)]
+pub async fn convert_render_page(rel: PathBuf, page: Option<(usize, usize)>) -> Result<(Vec<Page>, Prop), HeliumError> {
     let mut page_indices = Vec::new();
     let mut prop = Prop {paged: false, bottom_href: None, top_href: None};
 
@@ -304,7 +343,7 @@ pub async fn convert_render_page(rel: PathBuf, page: Option<(usize, usize)>) ->
             for i in s..e+1 {
                 let mut rel = rel.clone();
                 rel.pop();
-                rel.push(&i.to_string());
+                rel.push(i.to_string());
                 page_indices.push(Page { index: i, cursor: i == index as i32, href: rel.to_str().ok_or("cannot convert path to str")?.to_owned()});
             }
 
@@ -319,10 +358,10 @@ pub async fn convert_render_page(rel: PathBuf, page: Option<(usize, usize)>) ->
             prop.paged = true;
             let mut rel = rel.clone();
             rel.pop();
-            rel.push(&bottom.to_string());
+            rel.push(bottom.to_string());
             prop.bottom_href = Some(rel.to_str().ok_or("cannot convert path to str")?.to_owned());
             rel.pop();
-            rel.push(&top.to_string());
+            rel.push(top.to_string());
             prop.top_href = Some(rel.to_str().ok_or("cannot convert path to str")?.to_owned());
 
         },
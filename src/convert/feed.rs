@@ -0,0 +1,243 @@
+use std::{path::Path, sync::Arc};
+
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+use regex::Regex;
+use serde::Serialize;
+
+use crate::{build::Build, error::HeliumError, index::{flatten_file_node, Node, NodeType}, site::Site, util::{encode_path, fs::write_from_string, resolve_osstr_default}};
+
+use super::file::{Document, FileType};
+
+struct FeedEntry {
+    title: String,
+    url: String,
+    author: String,
+    date: DateTime<Utc>,
+    summary: String,
+    content: String,
+}
+
+struct Feed {
+    title: String,
+    /*
+     * url of the directory the feed files are written to, ends with a slash
+     */
+    home_url: String,
+    author: String,
+    entries: Vec<FeedEntry>,
+}
+
+/*
+ * created_at of the front matter, documents without it are not published in feeds
+ */
+pub fn created_at(doc: &Document) -> Option<DateTime<Utc>> {
+    let created_at = doc.property.created_at.as_ref()?;
+    let date = NaiveDate::parse_from_str(created_at, "%Y-%m-%d").ok()?;
+    Some(date.and_hms_opt(0, 0, 0)?.and_utc())
+}
+
+pub fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/*
+ * feed readers resolve nothing against the site, so root relative urls are made absolute
+ */
+fn absolute_urls(html: &str, base_url: &str) -> Result<String, HeliumError> {
+    let re = Regex::new(r#"(href|src)="/"#)?;
+    Ok(re.replace_all(html, format!("$1=\"{}/", base_url)).to_string())
+}
+
+async fn collect_entries(site: &Site, node: &Arc<Node>, base_url: &str) -> Result<Vec<FeedEntry>, HeliumError> {
+    let context = &site.context;
+    let profile = &site.template.profile;
+
+    let mut entries = Vec::new();
+    for node in flatten_file_node(node).await {
+        let abs_path = match &node.path.read().await.abs_path {
+            Some(abs_path) => abs_path.clone(),
+            None => return Err("abs_path is not ready".into()),
+        };
+        if let NodeType::File(lk) = &node.property.node_type {
+            if let Some(FileType::Markdown(fp, doc)) = &*lk.read().await {
+                let date = match created_at(doc) {
+                    Some(date) => date,
+                    None => continue,
+                };
+                let content = match (context.render.feed.full_content, &*doc.html.read().await) {
+                    (true, Some(rendered)) => absolute_urls(&rendered.html, base_url)?,
+                    _ => doc.parameter.summary.clone(),
+                };
+                entries.push(FeedEntry {
+                    title: doc.property.title.clone().unwrap_or(fp.key.clone()),
+                    url: format!("{}{}", base_url, encode_path(&abs_path)?),
+                    author: doc.property.author.clone().unwrap_or(profile.name().to_string()),
+                    date,
+                    summary: doc.parameter.summary.clone(),
+                    content,
+                });
+            }
+        }
+    }
+    entries.sort_by(|a, b| b.date.cmp(&a.date).then_with(|| a.url.cmp(&b.url)));
+    entries.truncate(context.render.feed.entries);
+
+    Ok(entries)
+}
+
+/*
+ * the feed is only as recent as its newest entry, so unchanged documents keep the file unchanged
+ */
+fn updated(feed: &Feed) -> DateTime<Utc> {
+    feed.entries.first().map(|entry| entry.date).unwrap_or_default()
+}
+
+fn atom(feed: &Feed) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    xml.push_str(&format!("<title>{}</title>\n", xml_escape(&feed.title)));
+    xml.push_str(&format!("<link href=\"{}\"/>\n", xml_escape(&feed.home_url)));
+    xml.push_str(&format!("<link rel=\"self\" href=\"{}feed.xml\"/>\n", xml_escape(&feed.home_url)));
+    xml.push_str(&format!("<id>{}</id>\n", xml_escape(&feed.home_url)));
+    xml.push_str(&format!("<updated>{}</updated>\n", updated(feed).to_rfc3339_opts(SecondsFormat::Secs, true)));
+    xml.push_str(&format!("<author><name>{}</name></author>\n", xml_escape(&feed.author)));
+    for entry in &feed.entries {
+        let date = entry.date.to_rfc3339_opts(SecondsFormat::Secs, true);
+        xml.push_str("<entry>\n");
+        xml.push_str(&format!("<title>{}</title>\n", xml_escape(&entry.title)));
+        xml.push_str(&format!("<link href=\"{}\"/>\n", xml_escape(&entry.url)));
+        xml.push_str(&format!("<id>{}</id>\n", xml_escape(&entry.url)));
+        xml.push_str(&format!("<published>{}</published>\n", date));
+        xml.push_str(&format!("<updated>{}</updated>\n", date));
+        xml.push_str(&format!("<author><name>{}</name></author>\n", xml_escape(&entry.author)));
+        xml.push_str(&format!("<summary type=\"html\">{}</summary>\n", xml_escape(&entry.summary)));
+        xml.push_str(&format!("<content type=\"html\">{}</content>\n", xml_escape(&entry.content)));
+        xml.push_str("</entry>\n");
+    }
+    xml.push_str("</feed>\n");
+    xml
+}
+
+fn rss(feed: &Feed) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n");
+    xml.push_str("<channel>\n");
+    xml.push_str(&format!("<title>{}</title>\n", xml_escape(&feed.title)));
+    xml.push_str(&format!("<link>{}</link>\n", xml_escape(&feed.home_url)));
+    xml.push_str(&format!("<description>{}</description>\n", xml_escape(&feed.title)));
+    xml.push_str(&format!("<atom:link href=\"{}rss.xml\" rel=\"self\" type=\"application/rss+xml\"/>\n", xml_escape(&feed.home_url)));
+    xml.push_str(&format!("<lastBuildDate>{}</lastBuildDate>\n", updated(feed).to_rfc2822()));
+    for entry in &feed.entries {
+        xml.push_str("<item>\n");
+        xml.push_str(&format!("<title>{}</title>\n", xml_escape(&entry.title)));
+        xml.push_str(&format!("<link>{}</link>\n", xml_escape(&entry.url)));
+        xml.push_str(&format!("<guid isPermaLink=\"true\">{}</guid>\n", xml_escape(&entry.url)));
+        xml.push_str(&format!("<pubDate>{}</pubDate>\n", entry.date.to_rfc2822()));
+        xml.push_str(&format!("<dc:creator>{}</dc:creator>\n", xml_escape(&entry.author)));
+        xml.push_str(&format!("<description>{}</description>\n", xml_escape(&entry.content)));
+        xml.push_str("</item>\n");
+    }
+    xml.push_str("</channel>\n");
+    xml.push_str("</rss>\n");
+    xml
+}
+
+#[derive(Serialize)]
+struct JsonFeed<'a> {
+    version: &'static str,
+    title: &'a str,
+    home_page_url: &'a str,
+    feed_url: String,
+    authors: Vec<JsonAuthor<'a>>,
+    items: Vec<JsonItem<'a>>,
+}
+#[derive(Serialize)]
+struct JsonAuthor<'a> {
+    name: &'a str,
+}
+#[derive(Serialize)]
+struct JsonItem<'a> {
+    id: &'a str,
+    url: &'a str,
+    title: &'a str,
+    content_html: &'a str,
+    summary: &'a str,
+    date_published: String,
+    authors: Vec<JsonAuthor<'a>>,
+}
+
+fn json(feed: &Feed) -> Result<String, HeliumError> {
+    let json_feed = JsonFeed {
+        version: "https://jsonfeed.org/version/1.1",
+        title: &feed.title,
+        home_page_url: &feed.home_url,
+        feed_url: format!("{}feed.json", feed.home_url),
+        authors: vec![JsonAuthor { name: &feed.author }],
+        items: feed.entries.iter().map(|entry| JsonItem {
+            id: &entry.url,
+            url: &entry.url,
+            title: &entry.title,
+            content_html: &entry.content,
+            summary: &entry.summary,
+            date_published: entry.date.to_rfc3339_opts(SecondsFormat::Secs, true),
+            authors: vec![JsonAuthor { name: &entry.author }],
+        }).collect(),
+    };
+    Ok(serde_json::to_string_pretty(&json_feed)?)
+}
+
+async fn write_feed(site: &Site, feed: &Feed, target: &Path) -> Result<(), HeliumError> {
+    write_from_string(&site.file_limit, &target.join("feed.xml"), atom(feed)).await?;
+    write_from_string(&site.file_limit, &target.join("rss.xml"), rss(feed)).await?;
+    write_from_string(&site.file_limit, &target.join("feed.json"), json(feed)?).await?;
+    Ok(())
+}
+
+/*
+ * atom, rss and json feeds of the latest documents, written after the documents are rendered
+ */
+pub async fn render_feeds(site: &Site, build: &Build) -> Result<(), HeliumError> {
+    let context = &site.context;
+    let base_url = match &context.base_url {
+        Some(base_url) => base_url.trim_end_matches('/'),
+        None => return Ok(()),
+    };
+    let profile = &site.template.profile;
+
+    let feed = Feed {
+        title: profile.page_title().to_string(),
+        home_url: format!("{}/", base_url),
+        author: profile.name().to_string(),
+        entries: collect_entries(site, &build.root, base_url).await?,
+    };
+    write_feed(site, &feed, &build.target).await?;
+
+    if !context.render.feed.per_directory {
+        return Ok(());
+    }
+    for node in &*build.root.children.read().await {
+        if let NodeType::File(_) = &node.property.node_type {
+            continue;
+        }
+        let path_lk = node.path.read().await;
+        let (abs_path, target_path) = match (&path_lk.abs_path, &path_lk.target_path) {
+            (Some(abs_path), Some(target_path)) => (abs_path, target_path),
+            _ => return Err("abs_path is not ready".into()),
+        };
+        let feed = Feed {
+            title: format!("{} - {}", profile.page_title(), resolve_osstr_default(path_lk.org_path.file_name())?),
+            home_url: format!("{}{}/", base_url, encode_path(abs_path)?),
+            author: profile.name().to_string(),
+            entries: collect_entries(site, node, base_url).await?,
+        };
+        write_feed(site, &feed, target_path).await?;
+    }
+
+    Ok(())
+}
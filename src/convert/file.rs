@@ -1,24 +1,28 @@
-use std::{borrow::Cow, collections::{HashMap, HashSet, VecDeque}, path::{Path, PathBuf}, sync::Arc};
+use std::{collections::{HashSet, VecDeque}, iter::Peekable, path::{Path, PathBuf}, sync::Arc, vec::IntoIter};
 
-use markdown::{mdast::{Code, Image, InlineCode, Text}, Options, ParseOptions};
-use regex::{Captures, RegexBuilder};
-use serde::Deserialize;
-use tokio::{fs::read_to_string, sync::RwLock};
+use async_recursion::async_recursion;
+use markdown::{mdast::{BlockQuote, Code, Heading, Image, InlineCode, InlineMath, Math, Text}, Options, ParseOptions};
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use tokio::{fs::read, sync::RwLock};
 
-use crate::{index::{Node}, util::{resolve_osstr, resolve_path, sem::Lock, token::get_tokenizer}};
+use crate::{cache::{self, content_hash, CacheEntry, RenderedHtml, CACHE_FORMAT, CACHE_VERSION}, context::Context, error::{HeliumError, Position}, index::{Node, NodeType, Resource, ResourceMap}, site::Site, util::{encode_path, resolve_osstr, resolve_path, sem::Lock}};
+
+use super::{highlight::Highlighter, math::latex_to_mathml, tag::{merge_tags, parse_inline_tags}};
 
 #[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
 pub enum FileType {
     Markdown(FileProperty, Document),
     Binary(FileProperty),
 }
 impl FileType {
-    pub async fn new(node: &Arc<Node>) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+    pub async fn new(site: &Site, node: &Arc<Node>) -> Result<Self, HeliumError> {
         let path = &node.path.read().await.org_path;
         match resolve_osstr(path.extension())? {
             "md" => {
                 let file_stem = resolve_osstr(path.file_stem())?;
-                Ok(FileType::Markdown(FileProperty::new(file_stem.into())?, Document::from_path(path).await?))
+                Ok(FileType::Markdown(FileProperty::new(file_stem.into())?, Document::from_path(site, path).await?))
             },
             _ => {
                 Ok(FileType::Binary(FileProperty::from_path(path)?))
@@ -32,10 +36,10 @@ pub struct FileProperty {
     pub key: String,
 }
 impl FileProperty {
-    fn new(key: String) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+    fn new(key: String) -> Result<Self, HeliumError> {
         Ok(FileProperty { key,})
     }
-    fn from_path(path: &Path) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+    fn from_path(path: &Path) -> Result<Self, HeliumError> {
         let file_name = resolve_osstr(path.file_name())?;
 
         Ok(FileProperty { key: file_name.into() })
@@ -44,12 +48,18 @@ impl FileProperty {
 
 #[derive(Debug)]
 pub struct Document {
+    pub path: PathBuf,
+    pub hash: String,
     pub raw: String,
+    /*
+     * lines taken by the front matter, raw starts at this line of the source file
+     */
+    pub line_offset: usize,
     pub property: DocumentProperty,
     pub parameter: DocumentParameter,
-    pub html: Option<String>,
+    pub html: RwLock<Option<RenderedHtml>>,
 }
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DocumentProperty {
     pub title: Option<String>,
     pub author: Option<String>,
@@ -57,6 +67,13 @@ pub struct DocumentProperty {
     pub created_at: Option<String>,
     pub tags: Option<Vec<String>>,
     pub priority: Option<usize>,
+    pub publish: Option<bool>,
+    pub draft: Option<bool>,
+}
+impl DocumentProperty {
+    pub fn is_draft(&self) -> bool {
+        self.draft == Some(true) || self.publish == Some(false)
+    }
 }
 #[derive(Debug)]
 pub struct DocumentParameter {
@@ -67,28 +84,266 @@ pub struct DocumentParameter {
     pub image_lk: Lock, 
 }
 
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum DocumentLinkType {
-    Resource(String),
-    Image(String),
+    Resource(DocumentLink),
+    Image(DocumentLink),
+}
+impl DocumentLinkType {
+    pub fn link(&self) -> &DocumentLink {
+        match self {
+            DocumentLinkType::Resource(link) | DocumentLinkType::Image(link) => link,
+        }
+    }
+}
+
+/*
+ * obsidian %%comments%% are private and left out of everything built from the note,
+ * a comment without an end runs to the end of the note as in obsidian
+ * newlines in a comment are kept, so the lines after it keep their position
+ */
+pub fn strip_comments(raw: &str, line_offset: usize) -> Result<String, HeliumError> {
+    /*
+     * %% inside code and math is left as it is, the mdast knows where they are
+     */
+    let mdast = markdown::to_mdast(raw, &parse_options()).map_err(|message| HeliumError::from_markdown(message, line_offset))?;
+    let mut code = Vec::new();
+    let mut q: VecDeque<&markdown::mdast::Node> = VecDeque::new();
+    q.push_back(&mdast);
+    while let Some(node) = q.pop_back() {
+        match node {
+            markdown::mdast::Node::Code(Code { position: Some(position), .. }) |
+                markdown::mdast::Node::InlineCode(InlineCode { position: Some(position), .. }) |
+                markdown::mdast::Node::Math(Math { position: Some(position), .. }) |
+                markdown::mdast::Node::InlineMath(InlineMath { position: Some(position), .. }) => {
+                    code.push(position.start.offset..position.end.offset);
+                },
+            _ => {
+                if let Some(children) = node.children() {
+                    q.extend(children.iter());
+                }
+            },
+        }
+    }
+
+    let mut stripped = String::with_capacity(raw.len());
+    let mut comment: Option<usize> = None;
+    let mut copied = 0;
+    let mut idx = 0;
+    while let Some(found) = raw[idx..].find("%%") {
+        let at = idx + found;
+        idx = at + 2;
+        if code.iter().any(|range| range.contains(&at)) {
+            continue;
+        }
+        match comment {
+            Some(start) => {
+                stripped.extend(raw[start..at].matches('\n'));
+                comment = None;
+                copied = idx;
+            },
+            None => {
+                stripped.push_str(&raw[copied..at]);
+                comment = Some(at);
+            },
+        }
+    }
+    match comment {
+        Some(start) => stripped.extend(raw[start..].matches('\n')),
+        None => stripped.push_str(&raw[copied..]),
+    }
+    Ok(stripped)
+}
+
+/*
+ * obsidian link grammar, [[folder/target#heading|alias]] or [[target#^block-id|alias]]
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DocumentLink {
+    pub target: String,
+    pub heading: Option<String>,
+    pub block: Option<String>,
+    pub alias: Option<String>,
+}
+impl DocumentLink {
+    pub fn parse(s: &str) -> Self {
+        /*
+         * pipes are escaped inside tables
+         */
+        let (link, alias) = match s.split_once('|') {
+            Some((link, alias)) => (link.trim_end_matches('\\'), Some(alias.trim().to_string())),
+            None => (s, None),
+        };
+        let (target, subpath) = match link.split_once('#') {
+            Some((target, subpath)) => (target, Some(subpath)),
+            None => (link, None),
+        };
+        let target = target.trim();
+        let target = target.strip_suffix(".md").unwrap_or(target).to_string();
+
+        let (heading, block) = match subpath {
+            Some(subpath) => match subpath.trim().strip_prefix('^') {
+                Some(block) => (None, Some(block.to_string())),
+                /*
+                 * nested headings point to the last one, [[target#h1#h2]]
+                 */
+                None => (subpath.rsplit('#').next().map(|heading| heading.trim().to_string()), None),
+            },
+            None => (None, None),
+        };
+
+        DocumentLink {
+            target,
+            heading: heading.filter(|heading| !heading.is_empty()),
+            block: block.filter(|block| !block.is_empty()),
+            alias: alias.filter(|alias| !alias.is_empty()),
+        }
+    }
+
+    /*
+     * key used by collect_resource, the file name without folders
+     */
+    pub fn key(&self) -> &str {
+        self.target.rsplit('/').next().unwrap_or(&self.target)
+    }
+
+    pub fn fragment(&self) -> String {
+        match (&self.heading, &self.block) {
+            (_, Some(block)) => format!("#^{}", block),
+            (Some(heading), None) => format!("#{}", heading_id(heading)),
+            (None, None) => String::new(),
+        }
+    }
+
+    pub fn display(&self) -> String {
+        if let Some(alias) = &self.alias {
+            return alias.clone();
+        }
+        let subpath = match (&self.heading, &self.block) {
+            (_, Some(block)) => Some(format!("^{}", block)),
+            (Some(heading), None) => Some(heading.clone()),
+            (None, None) => None,
+        };
+        match subpath {
+            Some(subpath) if self.target.is_empty() => subpath,
+            Some(subpath) => format!("{} > {}", self.target, subpath),
+            None => self.target.clone(),
+        }
+    }
+}
+
+/*
+ * id of a heading element, also used as the fragment of [[target#heading]]
+ * lowercased letters and digits with the words joined by -, markup and punctuation are left out
+ */
+pub fn heading_id(text: &str) -> String {
+    let mut id = String::new();
+    for c in text.chars() {
+        match c {
+            c if c.is_alphanumeric() || c == '_' => id.extend(c.to_lowercase()),
+            c if (c.is_whitespace() || c == '-') && !id.is_empty() && !id.ends_with('-') => id.push('-'),
+            _ => {},
+        }
+    }
+    match id.trim_end_matches('-') {
+        "" => "section".to_string(),
+        id => id.to_string(),
+    }
+}
+
+/*
+ * a heading of the table of contents, nested under the closest heading of a higher level
+ */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TocEntry {
+    pub id: String,
+    pub text: String,
+    pub level: u8,
+    pub children: Vec<TocEntry>,
+}
+
+fn escape_link_text(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('[', "\\[").replace(']', "\\]")
 }
 
 impl Document {
-    pub async fn from_path(path: &Path) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
-        let data = read_to_string(path).await?;
+    /*
+     * 1-based line and column in the source file of a byte offset in the document body
+     */
+    pub fn position(&self, offset: usize) -> Position {
+        let before = &self.raw[..offset];
+        let line = before.matches('\n').count() + 1;
+        let column = match before.rfind('\n') {
+            Some(idx) => before[idx + 1..].chars().count() + 1,
+            None => before.chars().count() + 1,
+        };
+        Position { line: line + self.line_offset, column }
+    }
+
+    /*
+     * drafts are left out of the site unless `--drafts` is given
+     */
+    pub fn is_excluded(&self, context: &Context) -> bool {
+        self.property.is_draft() && !context.drafts
+    }
+
+    pub async fn from_path(site: &Site, path: &Path) -> Result<Self, HeliumError> {
+        let data = read(path).await.map_err(|err| HeliumError::from(err).at(path))?;
+        let hash = content_hash(&data);
+
+        /*
+         * unchanged documents skip parsing and tokenizing
+         */
+        if let Some(entry) = cache::load(site, path, &hash).await {
+            return Ok(Document {
+                path: path.to_path_buf(),
+                hash,
+                raw: entry.raw,
+                line_offset: entry.line_offset,
+                property: entry.property,
+                parameter: DocumentParameter {
+                    token: entry.token,
+                    summary: entry.summary,
+                    link: entry.link,
+                    image: RwLock::new(entry.image),
+                    image_lk: Lock::new(&["image"])
+                },
+                html: RwLock::new(entry.html),
+            });
+        }
+        let data = String::from_utf8(data).map_err(|err| HeliumError::markdown(format!("not valid utf-8 ({})", err), None))?;
 
         /*
          * parse document property
          */
-        let (mut property, raw) = parse_property(&data).await?;
+        let (mut property, raw, line_offset) = parse_property(&data).await?;
+        let raw = strip_comments(&raw, line_offset)?;
         let title: String = resolve_osstr(path.file_stem())?.into();
         property.title = Some(title.clone());
 
         /*
          * parse document parameters
          */
-        let (raw_token, summary, link, image) = parse_parameter(&raw).await?;
-        let token = create_token(raw_token, title).await?;
+        let (raw_token, summary, link, image, tags) = parse_parameter(&raw, line_offset).await?;
+        property.tags = merge_tags(property.tags.take(), tags);
+        let token = create_token(site, raw_token, title).await?;
+
+        cache::store(site, CacheEntry {
+            version: CACHE_VERSION.into(),
+            format: CACHE_FORMAT,
+            tokenizer: site.tokenizer.id(),
+            path: path.to_path_buf(),
+            hash: hash.clone(),
+            raw: raw.clone(),
+            line_offset,
+            property: property.clone(),
+            token: token.clone(),
+            summary: summary.clone(),
+            link: link.clone(),
+            image: image.clone(),
+            html: None,
+        }).await?;
+
         let parameter = DocumentParameter {
             token,
             summary,
@@ -98,26 +353,32 @@ impl Document {
         };
 
         Ok(Document {
+            path: path.to_path_buf(),
+            hash,
             raw,
+            line_offset,
             property,
             parameter,
-            html: None,
+            html: RwLock::new(None),
         })
     }
 }
 
-async fn parse_property(data: &str) -> Result<(DocumentProperty, String), Box<dyn std::error::Error + Send + Sync>> {
+async fn parse_property(data: &str) -> Result<(DocumentProperty, String, usize), HeliumError> {
     let mut raw = String::new();
     let mut property = String::new();
+    let mut line_offset = 0;
     let mut flag = false;
     for (idx, line) in data.lines().enumerate() {
-        if (idx == 0 && line == "---") || (flag && line == "---") {
+        if line == "---" && (idx == 0 || flag) {
             flag = !flag;                
+            line_offset += 1;
             continue;
         }
         if flag {
             property.push_str(line);
             property.push('\n');
+            line_offset += 1;
         }
         else {
             raw.push_str(line);
@@ -126,10 +387,24 @@ async fn parse_property(data: &str) -> Result<(DocumentProperty, String), Box<dy
 
     }
 
-    let property: DocumentProperty = serde_yaml::from_str(&property)?;
-    Ok((property, raw))
+    /*
+     * the yaml starts below the opening ---
+     */
+    let yaml = property;
+    let property: DocumentProperty = serde_yaml::from_str(&yaml)
+        .map_err(|err| HeliumError::front_matter(err.to_string(), HeliumError::yaml_position(&err, 1)))?;
+    if let Some(created_at) = &property.created_at {
+        if let Err(err) = chrono::NaiveDate::parse_from_str(created_at, "%Y-%m-%d") {
+            let position = yaml.lines().position(|line| line.starts_with("created_at:")).map(|idx| Position {
+                line: idx + 2,
+                column: yaml.lines().nth(idx).and_then(|line| line.find(created_at.as_str())).unwrap_or(0) + 1,
+            });
+            return Err(HeliumError::front_matter(format!("invalid created_at \"{}\", expected YYYY-MM-DD ({})", created_at, err), position));
+        }
+    }
+    Ok((property, raw, line_offset))
 }
-pub fn parse_document_link(s: &str) -> Result<Vec<(usize, usize, DocumentLinkType)>, Box<dyn std::error::Error + Send + Sync>> {
+pub fn parse_document_link(s: &str) -> Result<Vec<(usize, usize, DocumentLinkType)>, HeliumError> {
     let mut res = Vec::new();
     let re = regex::Regex::new(r"\[\[([^\[\]]*)\]\]")?;
     for captures in re.captures_iter(s) {
@@ -139,56 +414,84 @@ pub fn parse_document_link(s: &str) -> Result<Vec<(usize, usize, DocumentLinkTyp
 
         let index = pos.start().checked_sub(1).unwrap_or(pos.start());
         // let prefix = if &self.raw[index..index+1] == "!" {"!"} else {""};
+        let link = DocumentLink::parse(name.as_str());
         match &s[index..index+1] {
             "!" => {
-                res.push((index,pos.end(), DocumentLinkType::Image(name.as_str().to_owned())));
+                res.push((index,pos.end(), DocumentLinkType::Image(link)));
             },
             _ => {
-                res.push((pos.start(),pos.end(), DocumentLinkType::Resource(name.as_str().to_owned())));
+                res.push((pos.start(),pos.end(), DocumentLinkType::Resource(link)));
             }
         }
     }
     Ok(res)
 }
-async fn parse_parameter(data: &str) -> Result<(HashSet<String>, String, Vec<(usize, usize, DocumentLinkType)>, Vec<String>), Box<dyn std::error::Error + Send + Sync>> {
+async fn parse_parameter(data: &str, line_offset: usize) -> Result<(HashSet<String>, String, Vec<(usize, usize, DocumentLinkType)>, Vec<String>, Vec<String>), HeliumError> {
     let mut raw_token = HashSet::new();
+    let mut tags = Vec::new();
     let mut summarize = Vec::new();
     let mut summarize_size = 0;
+    /*
+     * where the last summary piece ended in the source, when it ended in a word
+     */
+    let mut glue: Option<usize> = None;
     let mut link = Vec::new();
     let mut image = Vec::new();
 
-    let mdast = markdown::to_mdast(data, &ParseOptions::gfm()).map_err(|_|{"markdown AST build failed"})?;
+    let mdast = markdown::to_mdast(data, &parse_options()).map_err(|message| HeliumError::from_markdown(message, line_offset))?;
     let mut q: VecDeque<&markdown::mdast::Node> = VecDeque::new();
     q.push_back(&mdast);
     while let Some(node) = q.pop_back() {
         match node {
             markdown::mdast::Node::Text(Text { value, position }) => {
-                let values = value.split('\n');
-                for value in values {
+                tags.append(&mut parse_inline_tags(value)?);
+                let start = position.as_ref().map(|position| position.start.offset);
+                let text = summary_text(value)?;
+                for (idx, (value, text)) in value.split('\n').zip(text.split('\n')).enumerate() {
                     raw_token.insert(value.to_string());
                     if summarize_size < 300 {
-                        summarize.push(tera::escape_html(value.trim()));
+                        let piece = summary_marks(&tera::escape_html(strip_callout_marker(text.trim())));
+                        push_summary(&mut summarize, piece, idx == 0 && start.is_some() && start == glue && !value.starts_with(char::is_whitespace));
                         summarize_size += value.len();
                     }
                     else {
                         break;
                     }
                 }
-                match position {
-                    Some(position) => {
-                        let p = position.start.offset;
-                        let res = parse_document_link(value)?;
-                        let mut l = res.into_iter()
-                            .map(|(start, end, link)|{
-                                (p + start, p + end, link)
-                            })
-                        .collect::<Vec<(usize, usize, DocumentLinkType)>>();
-                        link.append(&mut l);
-                    },
-                    None => {
-
-                    }
+                glue = match value.ends_with(char::is_whitespace) {
+                    true => None,
+                    false => position.as_ref().map(|position| position.end.offset),
+                };
+                if let Some(position) = position {
+                    /*
+                     * links are found in the source, the value of a text in a quote lacks the > of its lines
+                     */
+                    let p = position.start.offset;
+                    let res = parse_document_link(&data[p..position.end.offset])?;
+                    let mut l = res.into_iter()
+                        .map(|(start, end, link)|{
+                            (p + start, p + end, link)
+                        })
+                    .collect::<Vec<(usize, usize, DocumentLinkType)>>();
+                    link.append(&mut l);
+                }
+            },
+            /*
+             * dollars that are not a formula are text, they stay joined to the words around them
+             */
+            markdown::mdast::Node::InlineMath(InlineMath { position: Some(position), .. }) if is_dollar_text(&data[position.start.offset..position.end.offset]) => {
+                let value = &data[position.start.offset..position.end.offset];
+                if summarize_size < 300 {
+                    push_summary(&mut summarize, tera::escape_html(value), glue == Some(position.start.offset));
+                    summarize_size += value.len();
                 }
+                glue = Some(position.end.offset);
+            },
+            /*
+             * tex source reads as noise in a summary, formulas are left out of it
+             */
+            markdown::mdast::Node::InlineMath(_) | markdown::mdast::Node::Math(_) => {
+                glue = None;
             },
             markdown::mdast::Node::InlineCode(InlineCode { value, .. }) |
                 markdown::mdast::Node::Code(Code { value, .. }) => {
@@ -202,6 +505,7 @@ async fn parse_parameter(data: &str) -> Result<(HashSet<String>, String, Vec<(us
                             break;
                         }
                     }
+                    glue = None;
                 },
                 markdown::mdast::Node::Image(Image { url, .. }) => {
                     image.push(url.clone());
@@ -209,13 +513,10 @@ async fn parse_parameter(data: &str) -> Result<(HashSet<String>, String, Vec<(us
             _ => {
             }
         } 
-        match node.children() {
-            Some(children) => {
-                for child in children.iter().rev() {
-                    q.push_back(child);
-                }
-            },
-            None => {}
+        if let Some(children) = node.children() {
+            for child in children.iter().rev() {
+                q.push_back(child);
+            }
         }
     }
     let mut summary = String::new();
@@ -223,99 +524,653 @@ async fn parse_parameter(data: &str) -> Result<(HashSet<String>, String, Vec<(us
         summary.push_str(&line);
         summary.push(' ');
     }
-    Ok((raw_token, summary, link, image))
+    Ok((raw_token, summary, link, image, tags))
 
 }
-pub async fn create_token(raw_token: HashSet<String>, title: String) -> Result<HashSet<String>, Box<dyn std::error::Error + Send + Sync>> {
-    let tokenizer = get_tokenizer().await?;
+pub async fn create_token(site: &Site, raw_token: HashSet<String>, title: String) -> Result<HashSet<String>, HeliumError> {
+    let tokenizer = &site.tokenizer;
     let mut token = HashSet::new();
 
-    let stop_words = stop_words::get(stop_words::LANGUAGE::Korean);
+    let stop_words = tokenizer.stop_words();
     for t in raw_token {
-        /*
-         * todo: tokens from spliting whitespaces ?
-         */
-
-        let _whitespace_token = t.split(|c: char| !c.is_alphabetic())
-            .filter(|s| !s.is_empty())
-            .filter(|s| !stop_words.contains(&s.to_string()))
-            .map(|s: &str| s.to_string()).collect::<Vec<String>>();
-
-        // token.extend(whitespace_token.into_iter());
-
         let res = tokenizer.tokenize(&t).await?;
-        // println!("from: {:?}, to: {:?}", &t, &res);
-        token.extend(res.data.into_iter().filter(|token|{!stop_words.contains(token)}));
+        token.extend(res.data);
     }
 
     let title_token = title.split(|c: char| !c.is_alphanumeric())
         .filter(|s| !s.is_empty())
-        .filter(|s| !stop_words.contains(&s.to_string()))
+        .filter(|s| !stop_words.contains(*s))
         .map(|s: &str| s.to_string()).collect::<Vec<String>>();
-    token.extend(title_token.into_iter());
+    token.extend(title_token);
 
     let res = tokenizer.tokenize(&title).await?;
-    token.extend(res.data.into_iter().filter(|token|{!stop_words.contains(token)}));
+    token.extend(res.data);
 
     Ok(token)
 }
 
-pub async fn convert_html(doc: &Document, resource_map: Arc<HashMap<String, PathBuf>>) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-    let mut raw = doc.raw.clone();
-    let mut image = doc.parameter.image.write().await;
-    let mut weight = 0;
+/*
+ * embeds nested deeper than this are rendered as plain links
+ */
+const MAX_EMBED_DEPTH: usize = 4;
+
+/*
+ * markdown with resolved links, embedded documents wait as placeholders until the html is ready
+ */
+struct ResolvedDocument {
+    raw: String,
+    embeds: Vec<String>,
+    images: Vec<String>,
+}
+
+fn embed_placeholder(idx: usize) -> String {
+    format!("\u{E000}embed{}\u{E000}", idx)
+}
+
+/*
+ * markdown replacing one wikilink or embed, embedded documents and images are collected into resolved
+ */
+async fn resolve_link(site: &Site, doc: &Document, link_type: &DocumentLinkType, resource_map: &ResourceMap, stack: &mut Vec<PathBuf>, resolved: &mut ResolvedDocument) -> Result<String, HeliumError> {
+    let target = match link_type {
+        DocumentLinkType::Resource(link) => {
+            let display = escape_link_text(&link.display());
+            /*
+             * [[#heading]] points into the document itself
+             */
+            let href = match link.target.is_empty() {
+                true => Some(String::new()),
+                false => match resource_map.resolve(link, &doc.path) {
+                    Some(resource) => Some(encode_path(&resource.abs_path)?),
+                    None => None,
+                },
+            };
+            match href {
+                Some(href) => format!("[{}]({}{})", display, href, link.fragment()),
+                None if resource_map.is_excluded(link) || resource_map.ignored(link).is_some() => display,
+                None => format!("[{}]({})", display, "/"),
+            }
+        },
+        DocumentLinkType::Image(link) => {
+            /*
+             * ![[image.png|300]] carries a size instead of an alt text
+             */
+            let alt = match &link.alias {
+                Some(alias) if !alias.split('x').all(|n| n.parse::<usize>().is_ok()) => alias.clone(),
+                _ => link.key().to_string(),
+            };
+            match resource_map.resolve(link, &doc.path) {
+                Some(resource) => {
+                    match embed_document(site, resource, link, resource_map, stack).await? {
+                        Some(html) => {
+                            resolved.embeds.push(html);
+                            embed_placeholder(resolved.embeds.len() - 1)
+                        },
+                        None => {
+                            resolved.images.push(resolve_path(&resource.abs_path)?.to_string());
+                            format!("![{}]({})", escape_link_text(&alt), encode_path(&resource.abs_path)?)
+                        }
+                    }
+                },
+                None if resource_map.is_excluded(link) || resource_map.ignored(link).is_some() => escape_link_text(&link.display()),
+                None => {
+                    format!("![{}]({})", escape_link_text(&alt), "/")
+                }
+            }
+        }
+    };
+    Ok(target)
+}
+
+#[async_recursion]
+async fn resolve_document(site: &Site, doc: &Document, resource_map: &ResourceMap, stack: &mut Vec<PathBuf>) -> Result<ResolvedDocument, HeliumError> {
+    let mut resolved = ResolvedDocument {
+        raw: doc.raw.clone(),
+        embeds: Vec::new(),
+        images: Vec::new(),
+    };
+    let mut weight: isize = 0;
     for (start, end, link_type) in &doc.parameter.link {
-        let start = start + weight;
-        let end = end + weight;
-        match link_type {
-            DocumentLinkType::Resource(name) => {
-                match resource_map.get(name) {
-                    Some(path) => {
-                        let target = format!("[{}]({})", &name, resolve_path(path)?.replace(' ', "%20"));
-                        raw.replace_range(start..end, &target);
-                        weight += target.len() - (end - start);
-                    },
-                    None => {
-                        let target = format!("[{}]({})", &name, "/");
-                        raw.replace_range(start..end, &target);
-                        weight += target.len() - (end - start);
-                    },
-                }  
+        /*
+         * a link that fails to resolve is reported at its place in the source file
+         */
+        let target = resolve_link(site, doc, link_type, resource_map, stack, &mut resolved).await
+            .map_err(|err| err.into_link(doc.position(*start)).at(&doc.path))?;
+        let start = (*start as isize + weight) as usize;
+        let end = (*end as isize + weight) as usize;
+        resolved.raw.replace_range(start..end, &target);
+        weight += target.len() as isize - (end - start) as isize;
+    }
+
+    Ok(resolved)
+}
+
+/*
+ * ![[note]] and ![[note#section]] inline the converted html of a markdown node
+ * returns None when the resource is not a markdown document
+ */
+async fn embed_document(site: &Site, resource: &Resource, link: &DocumentLink, resource_map: &ResourceMap, stack: &mut Vec<PathBuf>) -> Result<Option<String>, HeliumError> {
+    let lk = match &resource.node.property.node_type {
+        NodeType::File(lk) => lk.read().await,
+        NodeType::Dir(_) => return Ok(None),
+    };
+    let target = match &*lk {
+        Some(FileType::Markdown(_, target)) => target,
+        _ => return Ok(None),
+    };
+
+    let href = format!("{}{}", encode_path(&resource.abs_path)?, link.fragment());
+    let title = format!("<a href=\"{}\">{}</a>", href, tera::escape_html(&link.display()));
+
+    /*
+     * cycles and deep nesting stop at a link to the embedded note
+     */
+    if stack.contains(&target.path) || stack.len() > MAX_EMBED_DEPTH {
+        return Ok(Some(format!("<div class=\"embed embed-unresolved\"><div class=\"embed-title\">{}</div></div>", title)));
+    }
+    stack.push(target.path.clone());
+    let resolved = resolve_document(site, target, resource_map, stack).await?;
+    stack.pop();
+
+    let (html, _) = markdown_to_html(&resolved.raw, site.highlighter.as_deref())?;
+    let html = insert_embeds(html, &resolved.embeds);
+    let content = match (&link.heading, &link.block) {
+        (_, Some(block)) => extract_block(&html, block),
+        (Some(heading), None) => extract_section(&html, &heading_id(heading)),
+        (None, None) => Some(html.as_str()),
+    };
+    match content {
+        Some(content) => Ok(Some(format!("<div class=\"embed\"><div class=\"embed-title\">{}</div><div class=\"embed-content\">{}</div></div>", title, content))),
+        None => Ok(Some(format!("<div class=\"embed embed-unresolved\"><div class=\"embed-title\">{}</div></div>", title))),
+    }
+}
+
+fn insert_embeds(html: String, embeds: &[String]) -> String {
+    insert_placeholders(html, embeds, embed_placeholder)
+}
+
+fn insert_placeholders(mut html: String, blocks: &[String], placeholder: fn(usize) -> String) -> String {
+    for (idx, block) in blocks.iter().enumerate() {
+        let placeholder = placeholder(idx);
+        html = html.replace(&format!("<p>{}</p>", placeholder), block).replace(&placeholder, block);
+    }
+    html
+}
+
+fn block_placeholder(idx: usize) -> String {
+    format!("\u{E000}block{}\u{E000}", idx)
+}
+
+fn inline_placeholder(idx: usize) -> String {
+    format!("\u{E000}inline{}\u{E000}", idx)
+}
+
+/*
+ * gfm with $inline$ and $$block$$ math as written in obsidian
+ */
+fn parse_options() -> ParseOptions {
+    let mut options = ParseOptions::gfm();
+    options.constructs.math_text = true;
+    options.constructs.math_flow = true;
+    options
+}
+
+/*
+ * a single $ followed or preceded by a space is a dollar sign as in obsidian,
+ * so prices like $5 and $10 are not math
+ */
+fn is_dollar_text(source: &str) -> bool {
+    let inner = source.trim_start_matches('$').trim_end_matches('$');
+    !source.starts_with("$$") && (inner.starts_with(char::is_whitespace) || inner.ends_with(char::is_whitespace))
+}
+
+/*
+ * html rendered from the mdast, waiting as placeholders in the markdown until the rest is converted
+ */
+#[derive(Default)]
+struct Rendered {
+    blocks: Vec<String>,
+    inlines: Vec<String>,
+    /*
+     * source of each inline, stands in for it in the text of a heading
+     */
+    inline_texts: Vec<String>,
+}
+impl Rendered {
+    fn block(&mut self, html: String) -> String {
+        self.blocks.push(html);
+        block_placeholder(self.blocks.len() - 1)
+    }
+    fn inline(&mut self, html: String, text: &str) -> String {
+        self.inlines.push(html);
+        self.inline_texts.push(text.to_string());
+        inline_placeholder(self.inlines.len() - 1)
+    }
+}
+
+/*
+ * code blocks, math and callouts are rendered from the mdast and put back into the html like embeds,
+ * each one is replaced by a placeholder in the markdown
+ */
+fn render_nodes(raw: &str, highlighter: Option<&Highlighter>, rendered: &mut Rendered) -> Result<String, HeliumError> {
+    let mdast = markdown::to_mdast(raw, &parse_options()).map_err(|message| HeliumError::markdown(message.reason, None))?;
+    let mut replacements = Vec::new();
+    let mut q: VecDeque<&markdown::mdast::Node> = VecDeque::new();
+    q.push_back(&mdast);
+    while let Some(node) = q.pop_front() {
+        let replacement = match node {
+            markdown::mdast::Node::Code(Code { value, lang, position: Some(position), .. }) => match highlighter {
+                Some(highlighter) => Some((position, rendered.block(highlighter.highlight(value, lang.as_deref())?))),
+                None => None,
             },
-            DocumentLinkType::Image(name) => {
-                match resource_map.get(name) {
-                    Some(path) => {
-                        let target = format!("![{}]({})", &name, resolve_path(path)?.replace(' ', "%20"));
-                        raw.replace_range(start..end, &target);
-                        weight += target.len() - (end - start);
-
-                        /*
-                         * local images have higher priority than outside images
-                         */
-                        image.insert(0, resolve_path(path)?.into());
-                    },
-                    None => {
-                        let target = format!("![{}]({})", &name, "/");
-                        raw.replace_range(start..end, &target);
-                        weight += target.len() - (end - start);
-                    }
-                }  
+            markdown::mdast::Node::Math(Math { value, position: Some(position), .. }) => {
+                Some((position, rendered.block(latex_to_mathml(value, true))))
+            },
+            markdown::mdast::Node::InlineMath(InlineMath { value, position: Some(position) }) => {
+                let source = &raw[position.start.offset..position.end.offset];
+                /*
+                 * like obsidian, $$ in a paragraph is display math
+                 */
+                Some((position, match (source.starts_with("$$"), is_dollar_text(source)) {
+                    (true, _) => rendered.inline(latex_to_mathml(value, true), value),
+                    (false, false) => rendered.inline(latex_to_mathml(value, false), value),
+                    (false, true) => format!("\\${}\\$", &source[1..source.len() - 1]),
+                }))
+            },
+            markdown::mdast::Node::BlockQuote(BlockQuote { position: Some(position), .. }) => {
+                render_callout(raw, position.start.offset, position.end.offset, highlighter, rendered)?
+                    .map(|callout| (position, callout))
+            },
+            markdown::mdast::Node::Paragraph(_) | markdown::mdast::Node::Heading(_) | markdown::mdast::Node::TableCell(_) => {
+                replacements.extend(render_marks(raw, node, rendered));
+                None
+            },
+            _ => None,
+        };
+        match replacement {
+            Some((position, markdown)) => replacements.push((position.start.offset, position.end.offset, markdown)),
+            None => {
+                if let Some(children) = node.children() {
+                    q.extend(children);
+                }
+            },
+        }
+    }
+    replacements.sort_by_key(|(start, ..)| *start);
 
-            }
+    let mut replaced = String::new();
+    let mut cursor = 0;
+    for (start, end, markdown) in replacements {
+        replaced.push_str(&raw[cursor..start]);
+        replaced.push_str(&markdown);
+        cursor = end;
+    }
+    replaced.push_str(&raw[cursor..]);
+    Ok(replaced)
+}
+
+/*
+ * ==text== as <mark>, pairs of delimiters are looked for in the text of one paragraph, heading or table cell,
+ * so code and math keep their equals signs
+ */
+fn render_marks(raw: &str, node: &markdown::mdast::Node, rendered: &mut Rendered) -> Vec<(usize, usize, String)> {
+    let mut delimiters = Vec::new();
+    let mut q: VecDeque<&markdown::mdast::Node> = VecDeque::new();
+    q.push_back(node);
+    while let Some(node) = q.pop_back() {
+        match node {
+            markdown::mdast::Node::Text(Text { position: Some(position), .. }) => {
+                delimiters.append(&mut mark_delimiters(raw, position.start.offset, position.end.offset));
+            },
+            _ => {
+                if let Some(children) = node.children() {
+                    q.extend(children.iter().rev());
+                }
+            },
         }
     }
-    doc.parameter.image_lk.ready("image").await?;
 
-    let html = markdown::to_html_with_options(&raw, &Options::gfm()).map_err(|_|{"markdown to html failed"})?;
+    let mut marks = Vec::new();
+    for (open, close) in pair_marks(delimiters) {
+        marks.push((open, open + 2, rendered.inline("<mark>".into(), "")));
+        marks.push((close, close + 2, rendered.inline("</mark>".into(), "")));
+    }
+    marks
+}
+
+/*
+ * runs of exactly two = in text[start..end], with whether each one can open and close a highlight
+ * an opening == is followed by text and a closing one follows text, so `a == b` stays as it is
+ */
+fn mark_delimiters(text: &str, start: usize, end: usize) -> Vec<(usize, bool, bool)> {
+    let mut delimiters = Vec::new();
+    let mut idx = start;
+    while let Some(found) = text[idx..end].find('=') {
+        let offset = idx + found;
+        let run = text[offset..end].chars().take_while(|&c| c == '=').count();
+        idx = offset + run;
+        if run != 2 {
+            continue;
+        }
+        let before = text[..offset].chars().next_back().is_some_and(|c| !c.is_whitespace());
+        let after = text[offset + 2..].chars().next().is_some_and(|c| !c.is_whitespace());
+        delimiters.push((offset, after, before));
+    }
+    delimiters
+}
+
+/*
+ * offsets of the opening and closing == of each highlight
+ */
+fn pair_marks(delimiters: Vec<(usize, bool, bool)>) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    let mut open = None;
+    for (offset, can_open, can_close) in delimiters {
+        match open {
+            Some(start) if can_close => {
+                pairs.push((start, offset));
+                open = None;
+            },
+            _ if can_open => open = Some(offset),
+            _ => {},
+        }
+    }
+    pairs
+}
+
+/*
+ * text of the summary as it reads on the page, links show their display text and ^block-id markers are dropped
+ * lines are kept, so they pair with the lines of the value
+ */
+fn summary_text(value: &str) -> Result<String, HeliumError> {
+    let mut text = String::with_capacity(value.len());
+    let mut copied = 0;
+    for (start, end, link) in parse_document_link(value)? {
+        text.push_str(&value[copied..start]);
+        text.push_str(&link.link().display());
+        copied = end;
+    }
+    text.push_str(&value[copied..]);
+
+    let block = Regex::new(r"(?m)(^|[ \t]+)\^[A-Za-z0-9-]+[ \t]*$")?;
+    Ok(block.replace_all(&text, "").into_owned())
+}
+
+/*
+ * pieces of the summary are joined with a space, a piece continuing the word of the last one is appended to it
+ */
+fn push_summary(summarize: &mut Vec<String>, piece: String, joined: bool) {
+    match (joined, summarize.last_mut()) {
+        (true, Some(last)) => last.push_str(&piece),
+        _ => summarize.push(piece),
+    }
+}
+
+/*
+ * highlights of a line of the summary, the line is already escaped
+ */
+fn summary_marks(line: &str) -> String {
+    let mut marked = String::with_capacity(line.len());
+    let mut copied = 0;
+    for (open, close) in pair_marks(mark_delimiters(line, 0, line.len())) {
+        marked.push_str(&line[copied..open]);
+        marked.push_str("<mark>");
+        marked.push_str(&line[open + 2..close]);
+        marked.push_str("</mark>");
+        copied = close + 2;
+    }
+    marked.push_str(&line[copied..]);
+    marked
+}
+
+/*
+ * > [!type] title, a + or - after the type makes it foldable, open or closed
+ * the content goes through the pipeline again, so callouts nest
+ */
+fn render_callout(raw: &str, start: usize, end: usize, highlighter: Option<&Highlighter>, rendered: &mut Rendered) -> Result<Option<String>, HeliumError> {
+    let lines: Vec<&str> = raw[start..end].lines().map(strip_quote).collect();
+    let marker = Regex::new(r"^\[!([A-Za-z0-9_-]+)\]([+-]?)[ \t]*(.*)$")?;
+    let captures = match lines.first().and_then(|line| marker.captures(line)) {
+        Some(captures) => captures,
+        None => return Ok(None),
+    };
+    let kind = captures[1].to_lowercase();
+    let title = match captures[3].trim() {
+        "" => {
+            let mut chars = kind.chars();
+            chars.next().map(|c| c.to_uppercase().chain(chars).collect()).unwrap_or_default()
+        },
+        title => {
+            let title = render_nodes(title, highlighter, rendered)?;
+            let html = markdown::to_html_with_options(&title, &Options { parse: parse_options(), ..Options::gfm() })
+                .map_err(|message| HeliumError::markdown(message.reason, None))?;
+            html.trim().trim_start_matches("<p>").trim_end_matches("</p>").to_string()
+        },
+    };
+    let content = render_nodes(&lines[1..].join("\n"), highlighter, rendered)?;
+
+    let class = format!("class=\"callout callout-{}\" data-callout=\"{}\"", kind, kind);
+    let (open, close) = match &captures[2] {
+        "+" => (format!("<details {} open><summary class=\"callout-title\">{}</summary>", class, title), "</details>"),
+        "-" => (format!("<details {}><summary class=\"callout-title\">{}</summary>", class, title), "</details>"),
+        _ => (format!("<div {}><div class=\"callout-title\">{}</div>", class, title), "</div>"),
+    };
+    let open = rendered.block(format!("{}<div class=\"callout-content\">", open));
+    let close = rendered.block(format!("</div>{}", close));
+
+    /*
+     * the lines keep the indentation of the quote, so a callout in a list stays in it,
+     * and start on a line of their own, so they do not continue the paragraph before them
+     */
+    let indent: String = raw[..start].rsplit('\n').next().unwrap_or_default()
+        .chars()
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let callout = [open, String::new(), content, String::new(), close].join("\n");
+    Ok(Some(format!("\n{}{}", indent, callout.replace('\n', &format!("\n{}", indent)))))
+}
+
+/*
+ * summaries leave out the [!type] starting a callout
+ */
+fn strip_callout_marker(line: &str) -> &str {
+    let marker = line.strip_prefix("[!")
+        .and_then(|rest| rest.split_once(']'))
+        .filter(|(kind, _)| !kind.is_empty() && kind.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+    match marker {
+        Some((_, rest)) => rest.trim_start_matches(['+', '-']).trim_start(),
+        None => line,
+    }
+}
+
+/*
+ * a line of a block quote without its >, lazy continuation lines have none
+ */
+fn strip_quote(line: &str) -> &str {
+    match line.trim_start().strip_prefix('>') {
+        Some(line) => line.strip_prefix([' ', '\t']).unwrap_or(line),
+        None => line,
+    }
+}
+
+/*
+ * heading with the given id and everything up to the next heading of the same or a higher level
+ */
+fn extract_section<'a>(html: &'a str, id: &str) -> Option<&'a str> {
+    let start_re = Regex::new(&format!(r#"<h([1-6]) id="{}">"#, regex::escape(id))).ok()?;
+    let captures = start_re.captures(html)?;
+    let start = captures.get(0)?;
+    let level: usize = captures[1].parse().ok()?;
+
+    let next_re = Regex::new(r"<h([1-6])[ >]").ok()?;
+    let end = next_re.captures_iter(&html[start.end()..])
+        .find(|captures| captures[1].parse::<usize>().map(|l| l <= level).unwrap_or(false))
+        .and_then(|captures| captures.get(0))
+        .map(|m| start.end() + m.start())
+        .unwrap_or(html.len());
+    Some(&html[start.start()..end])
+}
+
+/*
+ * paragraph or list item carrying the ^block-id anchor
+ */
+fn extract_block<'a>(html: &'a str, block: &str) -> Option<&'a str> {
+    let pos = html.find(&format!("id=\"^{}\"", block))?;
+    let p = html[..pos].rfind("<p>");
+    let li = html[..pos].rfind("<li>");
+    let (start, close) = match (p, li) {
+        (Some(p), Some(li)) if li > p => (li, "</li>"),
+        (Some(p), _) => (p, "</p>"),
+        (None, Some(li)) => (li, "</li>"),
+        (None, None) => return None,
+    };
+    let end = pos + html[pos..].find(close)? + close.len();
+    Some(&html[start..end])
+}
+
+fn markdown_to_html(raw: &str, highlighter: Option<&Highlighter>) -> Result<(String, Vec<TocEntry>), HeliumError> {
+    let mut rendered = Rendered::default();
+    let raw = render_nodes(raw, highlighter, &mut rendered)?;
+    let headings = collect_headings(&raw, &rendered)?;
 
-    let mut html = Cow::Borrowed(&html);
-    for i in 1..7 {
-        let re = RegexBuilder::new(&format!(r"<h{}>(.*?)<\/h{}>", i, i)).dot_matches_new_line(true).build()?;
-        let result = re.replace_all(&html.to_owned(), |caps: &Captures| {
-            format!("<h{} id=\"{}\">{}</h{}>",i, &caps[1], &caps[1], i)
-        }).to_string();
-        html = Cow::Owned(result);
+    /*
+     * links are already replaced here, so the position would not match the source file
+     */
+    let options = Options {
+        parse: parse_options(),
+        ..Options::gfm()
+    };
+    let html = markdown::to_html_with_options(&raw, &options).map_err(|message| HeliumError::markdown(message.reason, None))?;
+
+    /*
+     * headings of the html are in the order of the mdast, the rendered blocks are not in yet
+     */
+    let mut ids = headings.iter().map(|heading| &heading.id);
+    let html = Regex::new(r"<h([1-6])>")?.replace_all(&html, |caps: &Captures| match ids.next() {
+        Some(id) => format!("<h{} id=\"{}\">", &caps[1], id),
+        None => caps[0].to_string(),
+    }).to_string();
+
+    /*
+     * task list items get the classes the default template styles
+     */
+    let html = Regex::new(r#"<li>(\s*<p>)?<input type="checkbox" "#)?
+        .replace_all(&html, r#"<li class="task-list-item">$1<input type="checkbox" class="task-list-item-checkbox" "#)
+        .replace("<ul>\n<li class=\"task-list-item\">", "<ul class=\"contains-task-list\">\n<li class=\"task-list-item\">");
+
+    let mut html = insert_placeholders(html, &rendered.blocks, block_placeholder);
+    for (idx, inline) in rendered.inlines.iter().enumerate() {
+        html = html.replace(&inline_placeholder(idx), inline);
     }
 
-    Ok(html.to_string())
+    /*
+     * block references, a trailing ^block-id marks the block as the target of [[note#^block-id]]
+     */
+    let block = Regex::new(r"(<p>|[ \t]+)\^([A-Za-z0-9-]+)[ \t]*(</p>|</li>|\n<ul>|\n<ol>)")?;
+    let html = block.replace_all(&html, |caps: &Captures| {
+        let prefix = if &caps[1] == "<p>" { "<p>" } else { "" };
+        format!("{}<span class=\"block-anchor\" id=\"^{}\"></span>{}", prefix, &caps[2], &caps[3])
+    });
+
+    Ok((html.to_string(), nest_headings(&mut headings.into_iter().peekable(), 0)))
+}
+
+/*
+ * headings in document order with unique ids, a repeated heading gets -1, -2 and so on
+ * so [[note#heading]] keeps pointing at the first one
+ */
+fn collect_headings(raw: &str, rendered: &Rendered) -> Result<Vec<TocEntry>, HeliumError> {
+    let mdast = markdown::to_mdast(raw, &parse_options()).map_err(|message| HeliumError::markdown(message.reason, None))?;
+    let mut headings = Vec::new();
+    let mut used = HashSet::new();
+    let mut q: VecDeque<&markdown::mdast::Node> = VecDeque::new();
+    q.push_back(&mdast);
+    while let Some(node) = q.pop_back() {
+        match node {
+            markdown::mdast::Node::Heading(Heading { depth, .. }) => {
+                let text = plain_text(node, rendered);
+                let base = heading_id(&text);
+                let mut id = base.clone();
+                let mut suffix = 0;
+                while !used.insert(id.clone()) {
+                    suffix += 1;
+                    id = format!("{}-{}", base, suffix);
+                }
+                headings.push(TocEntry { id, text: tera::escape_html(text.trim()), level: *depth, children: Vec::new() });
+            },
+            _ => {
+                if let Some(children) = node.children() {
+                    q.extend(children.iter().rev());
+                }
+            },
+        }
+    }
+    Ok(headings)
+}
+
+/*
+ * text of a heading as it reads, inline math counts as its source
+ */
+fn plain_text(node: &markdown::mdast::Node, rendered: &Rendered) -> String {
+    match node {
+        markdown::mdast::Node::Text(Text { value, .. }) |
+            markdown::mdast::Node::InlineCode(InlineCode { value, .. }) => {
+                let mut text = value.clone();
+                for (idx, inline) in rendered.inline_texts.iter().enumerate() {
+                    text = text.replace(&inline_placeholder(idx), inline);
+                }
+                text
+            },
+        _ => node.children()
+            .map(|children| children.iter().map(|child| plain_text(child, rendered)).collect())
+            .unwrap_or_default(),
+    }
+}
+
+fn nest_headings(headings: &mut Peekable<IntoIter<TocEntry>>, level: u8) -> Vec<TocEntry> {
+    let mut entries = Vec::new();
+    while let Some(mut entry) = headings.next_if(|entry| entry.level > level) {
+        entry.children = nest_headings(headings, entry.level);
+        entries.push(entry);
+    }
+    entries
+}
+
+pub async fn convert_html(site: &Site, doc: &Document, resource_map: Arc<ResourceMap>) -> Result<RenderedHtml, HeliumError> {
+    let mut stack = vec![doc.path.clone()];
+    let resolved = resolve_document(site, doc, &resource_map, &mut stack).await?;
+
+    let mut image = doc.parameter.image.write().await;
+    for path in resolved.images {
+        /*
+         * local images have higher priority than outside images
+         */
+        if !image.contains(&path) {
+            image.insert(0, path);
+        }
+    }
+    drop(image);
+    doc.parameter.image_lk.ready("image").await?;
+
+    /*
+     * html depends on the resolved links, embedded notes and highlighting as well, so it is keyed by them
+     */
+    let highlighter = site.highlighter.as_ref().map(|highlighter| highlighter.id()).unwrap_or_default();
+    let source_hash = content_hash(format!("{}{}{}", resolved.raw, resolved.embeds.concat(), highlighter).as_bytes());
+    if let Some(rendered) = &*doc.html.read().await {
+        if rendered.source_hash == source_hash {
+            return Ok(rendered.clone());
+        }
+    }
+
+    let (html, toc) = markdown_to_html(&resolved.raw, site.highlighter.as_deref())?;
+
+    let rendered = RenderedHtml {
+        source_hash,
+        html: insert_embeds(html, &resolved.embeds),
+        toc,
+    };
+    cache::store_html(site, &doc.path, &doc.hash, &rendered).await?;
+    *doc.html.write().await = Some(rendered.clone());
+
+    Ok(rendered)
 }
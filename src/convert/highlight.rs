@@ -0,0 +1,113 @@
+use syntect::{highlighting::{Theme, ThemeSet}, html::{css_for_theme_with_class_style, ClassStyle, ClassedHTMLGenerator}, parsing::SyntaxSet, util::LinesWithEndings};
+
+use crate::{context::Context, error::HeliumError};
+
+/*
+ * spans are annotated with the scopes of the syntax, the colors come from the css of the theme
+ */
+static CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+
+/*
+ * written next to the other static files when highlighting is enabled
+ */
+pub static HIGHLIGHT_CSS: &str = "highlight.css";
+
+/*
+ * build-time highlighting of fenced code blocks
+ */
+pub struct Highlighter {
+    syntaxes: SyntaxSet,
+    css: String,
+    line_numbers: bool,
+    id: String,
+}
+impl Highlighter {
+    /*
+     * none when the config has no `render.highlight`
+     */
+    pub fn new(context: &Context) -> Result<Option<Self>, HeliumError> {
+        let highlight = match &context.render.highlight {
+            Some(highlight) => highlight,
+            None => return Ok(None),
+        };
+        let themes = ThemeSet::load_defaults();
+        let find_theme = |key: &str, name: &str| -> Result<&Theme, HeliumError> {
+            themes.themes.get(name).ok_or_else(|| {
+                let known: Vec<&str> = themes.themes.keys().map(String::as_str).collect();
+                context.error_at(key, format!("unknown theme `{}`, expected one of `{}`", name, known.join("`, `")))
+            })
+        };
+
+        let mut css = theme_css(find_theme("theme:", &highlight.theme)?)?;
+        if let Some(dark_theme) = &highlight.dark_theme {
+            /*
+             * the template switches to dark mode with a `dark` class on the root element
+             */
+            let dark_css = theme_css(find_theme("dark_theme:", dark_theme)?)?;
+            for line in dark_css.lines() {
+                match line.starts_with('.') && line.ends_with('{') {
+                    true => {
+                        let selectors: Vec<String> = line.trim_end_matches('{').split(',')
+                            .map(|selector| format!(".dark {}", selector.trim()))
+                            .collect();
+                        css.push_str(&format!("{} {{\n", selectors.join(", ")));
+                    },
+                    false => {
+                        css.push_str(line);
+                        css.push('\n');
+                    },
+                }
+            }
+        }
+
+        Ok(Some(Highlighter {
+            syntaxes: SyntaxSet::load_defaults_newlines(),
+            css,
+            line_numbers: highlight.line_numbers,
+            id: format!("{}:{}:{}", highlight.theme, highlight.dark_theme.as_deref().unwrap_or_default(), highlight.line_numbers),
+        }))
+    }
+
+    pub fn css(&self) -> &str {
+        &self.css
+    }
+
+    /*
+     * identifies the produced html, rendered documents are cached with it
+     */
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /*
+     * a code block as pre.highlight, languages syntect does not know are shown as plain text
+     */
+    pub fn highlight(&self, code: &str, lang: Option<&str>) -> Result<String, HeliumError> {
+        let syntax = lang
+            .and_then(|lang| self.syntaxes.find_syntax_by_token(lang))
+            .unwrap_or_else(|| self.syntaxes.find_syntax_plain_text());
+        let mut generator = ClassedHTMLGenerator::new_with_class_style(syntax, &self.syntaxes, CLASS_STYLE);
+        let code = format!("{}\n", code);
+        for line in LinesWithEndings::from(&code) {
+            generator.parse_html_for_line_which_includes_newline(line).map_err(|err| HeliumError::markdown(format!("cannot highlight {} code ({})", lang.unwrap_or("plain"), err), None))?;
+        }
+        let spans = generator.finalize();
+
+        let class = match lang {
+            Some(lang) => format!(" class=\"language-{}\"", tera::escape_html(lang)),
+            None => String::new(),
+        };
+        let lines = match self.line_numbers {
+            true => {
+                let numbers: Vec<String> = (1..=code.lines().count()).map(|number| number.to_string()).collect();
+                format!("<span class=\"line-numbers\" aria-hidden=\"true\">{}\n</span>", numbers.join("\n"))
+            },
+            false => String::new(),
+        };
+        Ok(format!("<pre class=\"highlight hl-code\"><code{}>{}<span class=\"code\">{}</span></code></pre>", class, lines, spans))
+    }
+}
+
+fn theme_css(theme: &Theme) -> Result<String, HeliumError> {
+    css_for_theme_with_class_style(theme, CLASS_STYLE).map_err(|err| HeliumError::config(format!("cannot generate css of the theme ({})", err)))
+}
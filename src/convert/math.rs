@@ -0,0 +1,43 @@
+use katex::{Opts, OutputType};
+
+/*
+ * latex math to mathml with katex, so formulas need no script or font download in the browser
+ * a formula katex rejects is shown as an error inside the formula, with the reason
+ */
+pub fn latex_to_mathml(latex: &str, display: bool) -> String {
+    let opts = Opts::builder()
+        .display_mode(display)
+        .output_type(OutputType::Mathml)
+        .throw_on_error(true)
+        .build();
+    let rendered = opts.map_err(|err| err.to_string())
+        .and_then(|opts| katex::render_with_opts(latex, opts).map_err(|err| err.to_string()));
+    match rendered {
+        Ok(mathml) => mathml,
+        Err(message) => {
+            let display = match display {
+                true => " display=\"block\"",
+                false => "",
+            };
+            format!(
+                "<span class=\"katex\"><math xmlns=\"http://www.w3.org/1998/Math/MathML\"{}><merror><mtext>{} ({})</mtext></merror></math></span>",
+                display, tera::escape_html(latex), tera::escape_html(&parse_error(&message))
+            )
+        },
+    }
+}
+
+/*
+ * the reason of a katex parse error, without the engine wrapping it and the position katex underlines
+ */
+fn parse_error(message: &str) -> String {
+    let reason = match message.split_once("KaTeX parse error: ") {
+        Some((_, reason)) => reason,
+        None => return message.to_string(),
+    };
+    let end = [" at position ", " at end of input"].iter()
+        .filter_map(|marker| reason.find(marker))
+        .min()
+        .unwrap_or(reason.len());
+    reason[..end].replace("\\\\", "\\")
+}
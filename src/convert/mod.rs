@@ -1,11 +1,23 @@
-use std::{collections::HashMap, path::{PathBuf}, sync::Arc};
+use std::sync::Arc;
 
-use crate::{convert::render::create_post_page, index::{Node, NodeType}, util::{fs::copy_file, resolve_path}};
+use crate::{convert::render::create_post_page, error::HeliumError, index::{backlink::BacklinkMap, Node, NodeType, ResourceMap}, site::Site, util::{fs::copy_file, resolve_path}};
 
 use self::{dir::{convert_render_list, convert_render_page, DirType}, file::{convert_html, FileType}, render::create_index_page};
 
 pub mod dir;
+/*
+ * atom, rss and json feeds
+ */
+pub mod feed;
 pub mod file;
+/*
+ * build-time syntax highlighting of code blocks
+ */
+pub mod highlight;
+/*
+ * latex math to mathml
+ */
+pub mod math;
 /*
  * uses tera template engine for rendering
  */
@@ -14,6 +26,14 @@ pub mod render;
  * search index creation
  */
 pub mod search;
+/*
+ * sitemap.xml and robots.txt
+ */
+pub mod sitemap;
+/*
+ * tag collection and tag listing pages
+ */
+pub mod tag;
 
 
 
@@ -21,28 +41,36 @@ pub mod search;
 /*
  * prepare node of rendering by parsing the metadata
  */
-pub async fn prepare_node(node: &Arc<Node>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+pub async fn prepare_node(site: &Site, node: &Arc<Node>) -> Result<(), HeliumError> {
+    let result = parse_node(site, node).await;
+    /*
+     * the lock is readied even when parsing failed, so the parent waiting on it is not stuck
+     */
+    node.property.lk.ready("prepare").await?;
+    result
+}
+
+async fn parse_node(site: &Site, node: &Arc<Node>) -> Result<(), HeliumError> {
+    let org_path = node.path.read().await.org_path.clone();
     match &node.property.node_type {
         NodeType::Dir(lk) => {
-            let dir_type = DirType::new(node).await?;
+            let dir_type = DirType::new(&site.context, node).await.map_err(|err| err.at(&org_path))?;
             let mut lk = lk.write().await;
             *lk = Some(dir_type);
             
         },
         NodeType::File(lk) => {
-            let file_type = FileType::new(node).await?;
+            let file_type = FileType::new(site, node).await.map_err(|err| err.at(&org_path))?;
 
             let mut lk = lk.write().await;
             *lk = Some(file_type);
         }
     }
-    // lock ready
-    node.property.lk.ready("prepare").await?;
 
     Ok(())
 }
 
-pub async fn render_node(node: &Arc<Node>, resource_map: Arc<HashMap<String, PathBuf>>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+pub async fn render_node(site: &Site, node: &Arc<Node>, resource_map: Arc<ResourceMap>, backlink_map: Arc<BacklinkMap>) -> Result<(), HeliumError> {
     let path_lk = node.path.read().await;
     let mut abs_path = match &node.path.read().await.abs_path {
         Some(abs_path) => abs_path.clone(),
@@ -68,14 +96,9 @@ pub async fn render_node(node: &Arc<Node>, resource_map: Arc<HashMap<String, Pat
                 }
             };
             let refresh = match &*lk.read().await {
-                Some(DirType::Entry(dp)) => {
-                    if dp.is_paged {
-                        abs_path.push("1");
-                        Some(abs_path)
-                    }
-                    else {
-                        None
-                    }
+                Some(DirType::Entry(dp)) if dp.is_paged => {
+                    abs_path.push("1");
+                    Some(abs_path)
                 },
                 _ => {
                     None
@@ -83,16 +106,17 @@ pub async fn render_node(node: &Arc<Node>, resource_map: Arc<HashMap<String, Pat
             };
 
             target_path.push("index.html");
-            create_index_page(&target_path, refresh, &list, &page_indices, &prop).await?;
+            create_index_page(site, &target_path, refresh, &list, &page_indices, &prop).await.map_err(|err| err.at(&path_lk.org_path))?;
         },
         NodeType::File(lk) => {
             match &*lk.read().await {
                 Some(FileType::Markdown(_, doc)) => {
-                    let html = convert_html(doc, resource_map).await?;
-                    create_post_page(&target_path, &html, &doc.property).await?;
+                    let rendered = convert_html(site, doc, resource_map).await.map_err(|err| err.at(&path_lk.org_path))?;
+                    let backlinks = backlink_map.get(&path_lk.org_path).map_or(&[][..], |backlinks| &backlinks[..]);
+                    create_post_page(site, &target_path, &rendered.html, &rendered.toc, &doc.property, backlinks).await.map_err(|err| err.at(&path_lk.org_path))?;
                 },
                 Some(FileType::Binary(_)) => {
-                    copy_file(&path_lk.org_path, &target_path).await?;
+                    copy_file(&site.file_limit, &path_lk.org_path, &target_path).await?;
                 },
                 None => {
                     return Err("File not ready".into());
@@ -2,17 +2,53 @@ use std::path::{Path, PathBuf};
 
 use serde::{Deserialize, Serialize};
 use tera::Tera;
-use tokio::sync::OnceCell;
 
-use crate::{get_context, util::fs::write_from_string};
+use crate::{context::Context, error::HeliumError, index::backlink::Backlink, serve::LIVE_RELOAD_PATH, site::Site, util::fs::write_from_string};
 
-use super::file::DocumentProperty;
+use super::file::{DocumentProperty, TocEntry};
+
+/*
+ * every site is rendered with these, other templates are only included by them
+ */
+static TEMPLATES: [&str; 3] = ["layout.html", "post.html", "list.html"];
 
 pub struct Template {
     tera: Tera, 
     tera_context: tera::Context,
+    pub profile: Profile,
 }
 impl Template {
+    pub fn new(context: &Context) -> Result<Self, HeliumError> {
+        let mut tera = Tera::new(&context.render.template).map_err(|err| HeliumError::from(err).at(Path::new(&context.render.template)))?;
+        tera.autoescape_on(vec![]);
+        for name in TEMPLATES {
+            if !tera.get_template_names().any(|template| template == name) {
+                return Err(context.error_at("template:", format!("template glob `{}` matches no {}", context.render.template, name)));
+            }
+        }
+        let mut tera_context = tera::Context::new();
+
+        let profile = match &context.render.profile {
+            Some(path) => {
+                let yaml = std::fs::read_to_string(path).map_err(|err| HeliumError::from(err).at(path))?;
+                let mut profile: Profile = serde_yaml::from_str(&yaml).map_err(|err| {
+                    HeliumError::Config { path: Some(path.clone()), position: HeliumError::yaml_position(&err, 0), message: err.to_string() }
+                })?;
+                profile.fill();
+                profile
+            },
+            None => Profile::new(),
+        };
+        tera_context.insert("profile", &profile);
+        tera_context.insert("feed", &context.base_url.is_some());
+        tera_context.insert("highlight", &context.render.highlight.is_some());
+
+        Ok(Template {
+            tera,
+            tera_context,
+            profile,
+        })
+    }
     fn get_context(&self) -> tera::Context {
         self.tera_context.clone() 
     }
@@ -28,6 +64,12 @@ pub struct Profile {
     footer: Option<String>,
 }
 impl Profile {
+    pub fn page_title(&self) -> &str {
+        self.page_title.as_deref().unwrap_or_default()
+    }
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or_default()
+    }
     fn new() -> Self {
         let mut profile = Profile::default();
         profile.fill();
@@ -50,34 +92,20 @@ impl Profile {
 }
 
 
-static TEMPLATE: OnceCell<Template> = OnceCell::const_new();
-pub async fn get_template() -> &'static Template {
-    TEMPLATE.get_or_init(|| async {
-        let context = get_context().await;
-
-        let mut tera = Tera::new(&context.render.template).unwrap();
-        tera.autoescape_on(vec![]);
-        let mut tera_context = tera::Context::new();
-
-        if let Some(path) = &context.render.profile {
-            let yaml = std::fs::read_to_string(path).unwrap();
-            let mut profile: Profile = serde_yaml::from_str(&yaml).unwrap();         
-            profile.fill();
-            tera_context.insert("profile", &profile);
-        } else {
-            tera_context.insert("profile", &Profile::new());
-        }
-
-        Template {
-            tera,
-            tera_context
-        }
-    }).await
+/*
+ * pages served by `serve` reload themselves whenever a rebuild finishes
+ */
+fn inject_live_reload(mut commit: String) -> String {
+    let script = format!("<script>new EventSource(\"{}\").onmessage = () => window.location.reload();</script>", LIVE_RELOAD_PATH);
+    match commit.rfind("</body>") {
+        Some(idx) => commit.insert_str(idx, &script),
+        None => commit.push_str(&script),
+    }
+    commit
 }
 
-
-pub async fn create_post_page(target: &Path, markdown_html: &str, doc_property: &DocumentProperty) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let template = get_template().await; 
+pub async fn create_post_page(site: &Site, target: &Path, markdown_html: &str, toc: &[TocEntry], doc_property: &DocumentProperty, backlinks: &[Backlink]) -> Result<(), HeliumError> {
+    let template = &site.template;
 
     let mut tera_context = template.get_context();
     tera_context.insert("title", &doc_property.title);
@@ -86,9 +114,14 @@ pub async fn create_post_page(target: &Path, markdown_html: &str, doc_property:
     tera_context.insert("created_at", &doc_property.created_at);
     tera_context.insert("tags", &doc_property.tags);
     tera_context.insert("post", &markdown_html);
+    tera_context.insert("toc", toc);
+    tera_context.insert("backlinks", backlinks);
 
-    let commit = template.tera.render("post.html", &tera_context).unwrap();
-    write_from_string(target, commit).await?;
+    let mut commit = template.tera.render("post.html", &tera_context).map_err(|err| HeliumError::template("post.html", err))?;
+    if site.context.live_reload {
+        commit = inject_live_reload(commit);
+    }
+    write_from_string(&site.file_limit, target, commit).await?;
     
     Ok(())
 }
@@ -120,8 +153,8 @@ pub struct Prop {
 }
 
 
-pub async fn create_index_page(target: &Path, refresh: Option<PathBuf>, list: &Vec<List>, page_indices: &Vec<Page>, prop: &Prop) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let template = get_template().await; 
+pub async fn create_index_page(site: &Site, target: &Path, refresh: Option<PathBuf>, list: &Vec<List>, page_indices: &Vec<Page>, prop: &Prop) -> Result<(), HeliumError> {
+    let template = &site.template;
     
     let mut tera_context = template.get_context();
     tera_context.insert("refresh", &refresh);
@@ -129,7 +162,10 @@ pub async fn create_index_page(target: &Path, refresh: Option<PathBuf>, list: &V
     tera_context.insert("pages", page_indices);
     tera_context.insert("prop", prop);
 
-    let commit = template.tera.render("list.html", &tera_context)?;
-    write_from_string(target, commit).await?;
+    let mut commit = template.tera.render("list.html", &tera_context).map_err(|err| HeliumError::template("list.html", err))?;
+    if site.context.live_reload {
+        commit = inject_live_reload(commit);
+    }
+    write_from_string(&site.file_limit, target, commit).await?;
     Ok(()) 
 }
@@ -4,7 +4,7 @@ use async_recursion::async_recursion;
 use serde::{Deserialize, Serialize};
 use xorf::{HashProxy, Xor16};
 
-use crate::{index::{Node, NodeType}, util::{fs::write_from_slice, resolve_path}};
+use crate::{error::HeliumError, index::{Node, NodeType}, site::Site, util::{fs::write_from_slice, resolve_path}};
 
 use super::{file::FileType};
 
@@ -27,7 +27,7 @@ impl SearchIndex {
 }
 
 #[async_recursion]
-pub async fn render_search_index(node: Arc<Node>) -> Result<Vec<SearchIndex>, Box<dyn std::error::Error + Send + Sync>> {
+pub async fn render_search_index(site: &Site, node: Arc<Node>) -> Result<Vec<SearchIndex>, HeliumError> {
     let mut indices = Vec::new();
     let link = match &node.path.read().await.abs_path {
         Some(abs_path) => resolve_path(abs_path)?.into(),
@@ -42,22 +42,19 @@ pub async fn render_search_index(node: Arc<Node>) -> Result<Vec<SearchIndex>, Bo
     match &node.property.node_type {
         NodeType::Dir(_) => {
             for child in &*node.children.read().await {
-                indices.append(&mut render_search_index(child.clone()).await?);
+                indices.append(&mut render_search_index(site, child.clone()).await?);
             }
             let binary = bincode::serialize(&indices)?;
-            write_from_slice(&path.join("searchindex"), &binary[..]).await?;
+            write_from_slice(&site.file_limit, &path.join("searchindex"), &binary[..]).await?;
         },
         NodeType::File(lk) => {
-            match &*lk.read().await {
-                Some(FileType::Markdown(_, doc)) => {
-                    let title = match &doc.property.title {
-                        Some(title) => title.clone(),
-                        None => "undefined".to_owned(),
-                    };
-                    let search_index = SearchIndex::new(&doc.parameter.token, &title, link);
-                    indices.push(search_index);
-                },
-                _ => {}
+            if let Some(FileType::Markdown(_, doc)) = &*lk.read().await {
+                let title = match &doc.property.title {
+                    Some(title) => title.clone(),
+                    None => "undefined".to_owned(),
+                };
+                let search_index = SearchIndex::new(&doc.parameter.token, &title, link);
+                indices.push(search_index);
             }
         }
     }
@@ -0,0 +1,86 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+
+use crate::{build::Build, error::HeliumError, index::{flatten_file_node, flatten_node, Node, NodeType}, site::Site, util::{encode_path, fs::write_from_string}};
+
+use super::{feed::{created_at, xml_escape}, file::{Document, FileType}};
+
+/*
+ * created_at of the front matter, or the modification time of the source file
+ */
+async fn document_date(doc: &Document) -> Option<DateTime<Utc>> {
+    if let Some(date) = created_at(doc) {
+        return Some(date);
+    }
+    let modified = tokio::fs::metadata(&doc.path).await.ok()?.modified().ok()?;
+    Some(modified.into())
+}
+
+/*
+ * listings change whenever one of the documents below them does
+ */
+async fn latest_date(node: &Arc<Node>) -> Option<DateTime<Utc>> {
+    let mut latest = None;
+    for node in flatten_file_node(node).await {
+        if let NodeType::File(lk) = &node.property.node_type {
+            if let Some(FileType::Markdown(_, doc)) = &*lk.read().await {
+                latest = latest.max(document_date(doc).await);
+            }
+        }
+    }
+    latest
+}
+
+/*
+ * sitemap.xml with every post, listing and page, and robots.txt pointing at it
+ */
+pub async fn render_sitemap(site: &Site, build: &Build) -> Result<(), HeliumError> {
+    let base_url = match &site.context.base_url {
+        Some(base_url) => base_url.trim_end_matches('/'),
+        None => return Ok(()),
+    };
+
+    let mut urls = Vec::new();
+    for node in flatten_node(&build.root).await {
+        let abs_path = match &node.path.read().await.abs_path {
+            Some(abs_path) => encode_path(abs_path)?,
+            None => return Err("abs_path is not ready".into()),
+        };
+        match &node.property.node_type {
+            NodeType::Dir(_) => {
+                let loc = match abs_path.ends_with('/') {
+                    true => format!("{}{}", base_url, abs_path),
+                    false => format!("{}{}/", base_url, abs_path),
+                };
+                urls.push((loc, latest_date(&node).await));
+            },
+            NodeType::File(lk) => {
+                if let Some(FileType::Markdown(_, doc)) = &*lk.read().await {
+                    urls.push((format!("{}{}", base_url, abs_path), document_date(doc).await));
+                }
+            },
+        }
+    }
+    urls.sort();
+    urls.dedup_by(|a, b| a.0 == b.0);
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+    for (loc, lastmod) in urls {
+        xml.push_str("<url>\n");
+        xml.push_str(&format!("<loc>{}</loc>\n", xml_escape(&loc)));
+        if let Some(lastmod) = lastmod {
+            xml.push_str(&format!("<lastmod>{}</lastmod>\n", lastmod.format("%Y-%m-%d")));
+        }
+        xml.push_str("</url>\n");
+    }
+    xml.push_str("</urlset>\n");
+    write_from_string(&site.file_limit, &build.target.join("sitemap.xml"), xml).await?;
+
+    let robots = format!("User-agent: *\nAllow: /\n\nSitemap: {}/sitemap.xml\n", base_url);
+    write_from_string(&site.file_limit, &build.target.join("robots.txt"), robots).await?;
+
+    Ok(())
+}
@@ -0,0 +1,188 @@
+use std::{collections::BTreeMap, path::{Component, Path, PathBuf}, sync::Arc};
+
+use async_recursion::async_recursion;
+use regex::Regex;
+use tokio::{fs::create_dir_all, sync::RwLock};
+
+use crate::{build::Build, context::Context, error::HeliumError, index::{flatten_dir_node, flatten_file_node, Node, NodePath, NodeProperty, NodeType}, site::Site, util::sem::Lock};
+
+use super::{dir::{count_valid_children, page_children, sort_children, DirProperty, DirType}, render_node, FileType};
+
+/*
+ * tag pages are served from /tags, one directory per tag
+ */
+pub static TAGS_PATH: &str = "tags";
+
+/*
+ * inline #tag and #nested/tag in a text node, a tag needs at least one character that is not a digit
+ */
+pub fn parse_inline_tags(s: &str) -> Result<Vec<String>, HeliumError> {
+    let re = Regex::new(r"(?:^|\s)#([\p{L}\p{N}_\-/]+)")?;
+    let tags = re.captures_iter(s)
+        .filter_map(|captures| captures.get(1))
+        .map(|tag| tag.as_str().trim_matches('/').to_string())
+        .filter(|tag| !tag.is_empty() && !tag.chars().all(|c| c.is_ascii_digit() || c == '/'))
+        .collect();
+    Ok(tags)
+}
+
+/*
+ * front matter tags come first, tags written as "#tag" in the front matter are accepted too
+ */
+pub fn merge_tags(property_tags: Option<Vec<String>>, inline_tags: Vec<String>) -> Option<Vec<String>> {
+    let mut tags: Vec<String> = Vec::new();
+    let property_tags = property_tags.unwrap_or_default().into_iter()
+        .map(|tag| tag.trim().trim_start_matches('#').to_string());
+    for tag in property_tags.chain(inline_tags) {
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    match tags.is_empty() {
+        true => None,
+        false => Some(tags),
+    }
+}
+
+/*
+ * a document tagged #nested/tag is listed under #nested as well
+ */
+fn expand_tag(tag: &str) -> Vec<String> {
+    let mut tags = Vec::new();
+    let mut prefix = String::new();
+    for part in tag.split('/').filter(|part| !part.is_empty()) {
+        if !prefix.is_empty() {
+            prefix.push('/');
+        }
+        prefix.push_str(part);
+        tags.push(prefix.clone());
+    }
+    tags
+}
+
+fn dir_node(org_path: PathBuf, rel_path: PathBuf, children: Vec<Arc<Node>>) -> Arc<Node> {
+    Arc::new(Node {
+        property: NodeProperty {
+            node_type: NodeType::Dir(RwLock::new(None)),
+            lk: Lock::new(&["prepare"]),
+        },
+        path: RwLock::new(NodePath {
+            org_path,
+            rel_path,
+            abs_path: None,
+            target_path: None,
+        }),
+        children: RwLock::new(children),
+    })
+}
+
+/*
+ * same as DirType::new, but keyed by the tag instead of a source directory
+ */
+async fn prepare_tag_node(context: &Context, node: &Arc<Node>, key: String) -> Result<(), HeliumError> {
+    sort_children(context, node).await?;
+    let child_node_size = count_valid_children(&*node.children.read().await).await?;
+    let is_paged = page_children(node, context.render.list_size).await?;
+
+    if let NodeType::Dir(lk) = &node.property.node_type {
+        *lk.write().await = Some(DirType::Entry(DirProperty {
+            key,
+            child_node_size,
+            is_paged,
+        }));
+    }
+    node.property.lk.ready("prepare").await?;
+    Ok(())
+}
+
+/*
+ * tag nodes share the document nodes of the vault tree, so only directories are given paths here
+ * page nodes are served below their directory, the tags they list stay at /tags/<tag>/
+ */
+#[async_recursion]
+async fn init_tag_path(node: &Arc<Node>, abs_path: PathBuf, target_path: PathBuf) -> Result<(), HeliumError> {
+    for child in &*node.children.read().await {
+        if let NodeType::Dir(lk) = &child.property.node_type {
+            let rel_path: PathBuf = child.path.read().await.rel_path.components()
+                .filter(|component| !matches!(component, Component::CurDir))
+                .collect();
+            let is_page = matches!(&*lk.read().await, Some(DirType::Page(_, _)));
+            match is_page {
+                true => {
+                    for tag in &*child.children.read().await {
+                        if let NodeType::Dir(_) = &tag.property.node_type {
+                            let rel_path = tag.path.read().await.rel_path.clone();
+                            init_tag_path(tag, abs_path.join(&rel_path), target_path.join(&rel_path)).await?;
+                        }
+                    }
+                    let mut path_lk = child.path.write().await;
+                    path_lk.abs_path = Some(abs_path.join(&rel_path));
+                    path_lk.target_path = Some(target_path.join(&rel_path));
+                },
+                false => init_tag_path(child, abs_path.join(&rel_path), target_path.join(&rel_path)).await?,
+            }
+        }
+    }
+    let mut path_lk = node.path.write().await;
+    path_lk.abs_path = Some(abs_path);
+    path_lk.target_path = Some(target_path);
+    Ok(())
+}
+
+/*
+ * tree of tag nodes, the root lists every top level tag and each tag lists its documents and nested tags
+ */
+pub async fn build_tag_tree(context: &Context, root: &Arc<Node>, target_base: &Path) -> Result<Arc<Node>, HeliumError> {
+    let mut tagged: BTreeMap<String, Vec<Arc<Node>>> = BTreeMap::new();
+    for node in flatten_file_node(root).await {
+        if let NodeType::File(lk) = &node.property.node_type {
+            if let Some(FileType::Markdown(_, doc)) = &*lk.read().await {
+                let mut tags: Vec<String> = doc.property.tags.iter().flatten()
+                    .flat_map(|tag| expand_tag(tag))
+                    .collect();
+                tags.sort();
+                tags.dedup();
+                for tag in tags {
+                    tagged.entry(tag).or_default().push(node.clone());
+                }
+            }
+        }
+    }
+
+    /*
+     * nested tags come after their parent, so in reverse they are prepared before the parent sorts them
+     */
+    let mut tag_nodes: BTreeMap<String, Arc<Node>> = BTreeMap::new();
+    for (tag, documents) in tagged {
+        let name = tag.rsplit('/').next().unwrap_or(&tag).to_string();
+        tag_nodes.insert(tag.clone(), dir_node(PathBuf::from(&tag), PathBuf::from(name), documents));
+    }
+    let mut top_level = Vec::new();
+    for (tag, tag_node) in tag_nodes.iter().rev() {
+        prepare_tag_node(context, tag_node, tag.clone()).await?;
+        match tag.rsplit_once('/').and_then(|(parent, _)| tag_nodes.get(parent)) {
+            Some(parent) => parent.children.write().await.push(tag_node.clone()),
+            None => top_level.push(tag_node.clone()),
+        }
+    }
+    let tag_root = dir_node(PathBuf::from(TAGS_PATH), PathBuf::from(TAGS_PATH), top_level);
+    prepare_tag_node(context, &tag_root, TAGS_PATH.into()).await?;
+
+    init_tag_path(&tag_root, Path::new("/").join(TAGS_PATH), target_base.join(TAGS_PATH)).await?;
+
+    Ok(tag_root)
+}
+
+/*
+ * render /tags/index.html and the pages of every tag
+ */
+pub async fn render_tags(site: &Site, build: &Build) -> Result<(), HeliumError> {
+    let tag_root = build_tag_tree(&site.context, &build.root, &build.target).await?;
+    for node in flatten_dir_node(&tag_root).await {
+        if let Some(target_path) = &node.path.read().await.target_path {
+            create_dir_all(target_path).await?;
+        }
+        render_node(site, &node, build.resource_map.clone(), build.backlink_map.clone()).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,255 @@
+use std::{fmt, path::{Path, PathBuf}};
+
+use markdown::message::Place;
+
+/*
+ * 1-based line and column in a source file
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+/*
+ * everything that stops a build, printed as a diagnostic pointing at the file that broke
+ * the path is filled in by the caller that knows which file was being handled
+ */
+#[derive(Debug)]
+pub enum HeliumError {
+    Config { path: Option<PathBuf>, position: Option<Position>, message: String },
+    Io { path: Option<PathBuf>, source: std::io::Error },
+    FrontMatter { path: Option<PathBuf>, position: Option<Position>, message: String },
+    Markdown { path: Option<PathBuf>, position: Option<Position>, message: String },
+    Template { path: Option<PathBuf>, name: Option<String>, message: String },
+    Tokenizer { path: Option<PathBuf>, message: String },
+    Link { path: Option<PathBuf>, position: Option<Position>, message: String },
+    Internal { path: Option<PathBuf>, message: String },
+}
+impl HeliumError {
+    pub fn config(message: impl Into<String>) -> Self {
+        HeliumError::Config { path: None, position: None, message: message.into() }
+    }
+    pub fn front_matter(message: impl Into<String>, position: Option<Position>) -> Self {
+        HeliumError::FrontMatter { path: None, position, message: message.into() }
+    }
+    pub fn markdown(message: impl Into<String>, position: Option<Position>) -> Self {
+        HeliumError::Markdown { path: None, position, message: message.into() }
+    }
+    pub fn template(name: &str, err: tera::Error) -> Self {
+        HeliumError::Template { path: None, name: Some(name.to_string()), message: error_chain(&err) }
+    }
+    pub fn tokenizer(message: impl Into<String>) -> Self {
+        HeliumError::Tokenizer { path: None, message: message.into() }
+    }
+    pub fn link(message: impl Into<String>, position: Option<Position>) -> Self {
+        HeliumError::Link { path: None, position, message: message.into() }
+    }
+
+    /*
+     * yaml errors carry the position of the offending value, shifted by the lines before the yaml starts
+     */
+    pub fn yaml_position(err: &serde_yaml::Error, line_offset: usize) -> Option<Position> {
+        err.location().map(|location| Position {
+            line: location.line() + line_offset,
+            column: location.column(),
+        })
+    }
+
+    /*
+     * markdown messages point into the document body, which starts line_offset lines into the file
+     */
+    pub fn from_markdown(message: markdown::message::Message, line_offset: usize) -> Self {
+        let point = match message.place.as_deref() {
+            Some(Place::Point(point)) => Some(point),
+            Some(Place::Position(position)) => Some(&position.start),
+            None => None,
+        };
+        let position = point.map(|point| Position {
+            line: point.line + line_offset,
+            column: point.column,
+        });
+        HeliumError::markdown(message.reason, position)
+    }
+
+    /*
+     * attach the file being handled, errors that already name a file keep it
+     */
+    pub fn at(mut self, file: &Path) -> Self {
+        match &mut self {
+            HeliumError::Config { path, .. } |
+                HeliumError::Io { path, .. } |
+                HeliumError::FrontMatter { path, .. } |
+                HeliumError::Markdown { path, .. } |
+                HeliumError::Template { path, .. } |
+                HeliumError::Tokenizer { path, .. } |
+                HeliumError::Link { path, .. } |
+                HeliumError::Internal { path, .. } => {
+                    if path.is_none() {
+                        *path = Some(file.to_path_buf());
+                    }
+                },
+        }
+        self
+    }
+
+    /*
+     * failures inside a link keep their message but point at the link
+     */
+    pub fn into_link(self, position: Position) -> Self {
+        match self {
+            HeliumError::Link { .. } => self,
+            err => HeliumError::link(err.message(), Some(position)),
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            HeliumError::Config { message, .. } |
+                HeliumError::FrontMatter { message, .. } |
+                HeliumError::Markdown { message, .. } |
+                HeliumError::Link { message, .. } |
+                HeliumError::Tokenizer { message, .. } |
+                HeliumError::Internal { message, .. } => message.clone(),
+            HeliumError::Io { source, .. } => source.to_string(),
+            HeliumError::Template { name: Some(name), message, .. } => format!("{} in {}", message, name),
+            HeliumError::Template { name: None, message, .. } => message.clone(),
+        }
+    }
+
+    fn path(&self) -> Option<&Path> {
+        match self {
+            HeliumError::Config { path, .. } |
+                HeliumError::Io { path, .. } |
+                HeliumError::FrontMatter { path, .. } |
+                HeliumError::Markdown { path, .. } |
+                HeliumError::Template { path, .. } |
+                HeliumError::Tokenizer { path, .. } |
+                HeliumError::Link { path, .. } |
+                HeliumError::Internal { path, .. } => path.as_deref(),
+        }
+    }
+
+    fn position(&self) -> Option<Position> {
+        match self {
+            HeliumError::Config { position, .. } |
+                HeliumError::FrontMatter { position, .. } |
+                HeliumError::Markdown { position, .. } |
+                HeliumError::Link { position, .. } => *position,
+            _ => None,
+        }
+    }
+
+    /*
+     * internal errors are invariants of helium itself, they have no kind to show
+     */
+    fn kind(&self) -> Option<&'static str> {
+        match self {
+            HeliumError::Config { .. } => Some("config"),
+            HeliumError::Io { .. } => Some("io"),
+            HeliumError::FrontMatter { .. } => Some("front-matter"),
+            HeliumError::Markdown { .. } => Some("markdown"),
+            HeliumError::Template { .. } => Some("template"),
+            HeliumError::Tokenizer { .. } => Some("tokenizer"),
+            HeliumError::Link { .. } => Some("link"),
+            HeliumError::Internal { .. } => None,
+        }
+    }
+}
+
+/*
+ * tera hides the actual cause, like an unknown variable, in the source chain
+ */
+fn error_chain(err: &dyn std::error::Error) -> String {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(err) = source {
+        message.push_str(": ");
+        message.push_str(&err.to_string());
+        source = err.source();
+    }
+    message
+}
+
+impl fmt::Display for HeliumError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind() {
+            Some(kind) => write!(f, "error[{}]: {}", kind, self.message())?,
+            None => write!(f, "error: {}", self.message())?,
+        }
+        match (self.path(), self.position()) {
+            (Some(path), Some(position)) => write!(f, "\n  --> {}:{}:{}", path.display(), position.line, position.column),
+            (Some(path), None) => write!(f, "\n  --> {}", path.display()),
+            (None, _) => Ok(()),
+        }
+    }
+}
+
+impl std::error::Error for HeliumError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            HeliumError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for HeliumError {
+    fn from(source: std::io::Error) -> Self {
+        HeliumError::Io { path: None, source }
+    }
+}
+impl From<walkdir::Error> for HeliumError {
+    fn from(err: walkdir::Error) -> Self {
+        let path = err.path().map(Path::to_path_buf);
+        match err.into_io_error() {
+            Some(source) => HeliumError::Io { path, source },
+            None => HeliumError::Internal { path, message: "filesystem loop while walking the vault".into() },
+        }
+    }
+}
+impl From<tera::Error> for HeliumError {
+    fn from(err: tera::Error) -> Self {
+        HeliumError::Template { path: None, name: None, message: error_chain(&err) }
+    }
+}
+impl From<std::convert::Infallible> for HeliumError {
+    fn from(err: std::convert::Infallible) -> Self {
+        match err {}
+    }
+}
+impl From<&str> for HeliumError {
+    fn from(message: &str) -> Self {
+        HeliumError::Internal { path: None, message: message.into() }
+    }
+}
+impl From<String> for HeliumError {
+    fn from(message: String) -> Self {
+        HeliumError::Internal { path: None, message }
+    }
+}
+
+/*
+ * failures of libraries that have nothing to point at in the vault
+ */
+macro_rules! internal_from {
+    ($($err:ty),* $(,)?) => {
+        $(
+            impl From<$err> for HeliumError {
+                fn from(err: $err) -> Self {
+                    HeliumError::Internal { path: None, message: err.to_string() }
+                }
+            }
+        )*
+    };
+}
+internal_from!(
+    regex::Error,
+    serde_json::Error,
+    bincode::Error,
+    std::string::FromUtf8Error,
+    std::path::StripPrefixError,
+    tokio::task::JoinError,
+    tokio::sync::AcquireError,
+    notify::Error,
+);
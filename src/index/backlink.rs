@@ -0,0 +1,125 @@
+use std::{collections::HashMap, path::{Path, PathBuf}};
+
+use serde::Serialize;
+
+use crate::{convert::file::{Document, FileType}, error::HeliumError, index::{NodeType, Resource, ResourceMap}, util::encode_path};
+
+/*
+ * document linking to another one, with the sentences its links appear in
+ */
+#[derive(Serialize, Debug, Clone)]
+pub struct Backlink {
+    pub link: String,
+    pub title: String,
+    pub contexts: Vec<String>,
+}
+/*
+ * incoming links keyed by the source path of the linked document
+ */
+pub type BacklinkMap = HashMap<PathBuf, Vec<Backlink>>;
+
+async fn is_document(resource: &Resource) -> bool {
+    match &resource.node.property.node_type {
+        NodeType::File(lk) => matches!(&*lk.read().await, Some(FileType::Markdown(_, _))),
+        NodeType::Dir(_) => false,
+    }
+}
+
+/*
+ * invert the outgoing links of every document
+ */
+pub async fn collect_backlink(resource_map: &ResourceMap) -> Result<BacklinkMap, HeliumError> {
+    let mut backlink_map: BacklinkMap = HashMap::new();
+
+    let mut sources: Vec<(&String, &Resource)> = resource_map.iter().collect();
+    sources.sort_by(|(_, a), (_, b)| a.abs_path.cmp(&b.abs_path));
+    for (key, resource) in sources {
+        let lk = match &resource.node.property.node_type {
+            NodeType::File(lk) => lk.read().await,
+            NodeType::Dir(_) => continue,
+        };
+        let doc = match &*lk {
+            Some(FileType::Markdown(_, doc)) => doc,
+            _ => continue,
+        };
+
+        let mut incoming: Vec<(&Path, Vec<String>)> = Vec::new();
+        for (start, end, link_type) in &doc.parameter.link {
+            let link = link_type.link();
+            if link.target.is_empty() {
+                continue;
+            }
+            let target = match resource_map.resolve(link, &doc.path) {
+                Some(target) if target.org_path != resource.org_path && is_document(target).await => &target.org_path,
+                _ => continue,
+            };
+
+            let context = link_context(doc, *start, *end);
+            match incoming.iter_mut().find(|(key, _)| *key == target) {
+                Some((_, contexts)) => {
+                    if !contexts.contains(&context) {
+                        contexts.push(context);
+                    }
+                },
+                None => incoming.push((target, vec![context])),
+            }
+        }
+
+        for (target, contexts) in incoming {
+            backlink_map.entry(target.to_path_buf()).or_default().push(Backlink {
+                link: encode_path(&resource.abs_path)?,
+                title: doc.property.title.clone().unwrap_or(key.clone()),
+                contexts,
+            });
+        }
+    }
+
+    Ok(backlink_map)
+}
+
+/*
+ * sentence around a link, links inside it are replaced by their display text
+ */
+fn link_context(doc: &Document, start: usize, end: usize) -> String {
+    let raw = &doc.raw;
+    let links = &doc.parameter.link;
+    let is_sentence_end = |idx: usize, c: char| {
+        match c {
+            '\n' => true,
+            '.' | '!' | '?' => {
+                /*
+                 * dots inside a link target or a number do not end a sentence
+                 */
+                !links.iter().any(|(s, e, _)| *s <= idx && idx < *e)
+                    && raw[idx + c.len_utf8()..].chars().next().is_none_or(char::is_whitespace)
+            },
+            _ => false,
+        }
+    };
+
+    let begin = raw[..start].char_indices().rev()
+        .find(|(idx, c)| is_sentence_end(*idx, *c))
+        .map_or(0, |(idx, c)| idx + c.len_utf8());
+    let finish = raw[end..].char_indices()
+        .find(|(idx, c)| is_sentence_end(end + idx, *c))
+        .map_or(raw.len(), |(idx, c)| {
+            match c {
+                '\n' => end + idx,
+                _ => end + idx + c.len_utf8(),
+            }
+        });
+
+    let mut inner: Vec<_> = links.iter().filter(|(s, e, _)| begin <= *s && *e <= finish).collect();
+    inner.sort_by_key(|(s, _, _)| *s);
+    let mut context = String::new();
+    let mut cursor = begin;
+    for (s, e, link_type) in inner {
+        context.push_str(&raw[cursor..*s]);
+        context.push_str(&link_type.link().display());
+        cursor = *e;
+    }
+    context.push_str(&raw[cursor..finish]);
+
+    let context = context.trim().trim_start_matches(['#', '>', '-', '*', '+', ' ', '\t']);
+    tera::escape_html(context)
+}
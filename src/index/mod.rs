@@ -1,10 +1,17 @@
-use std::{collections::{HashMap, VecDeque}, path::{Path, PathBuf}, str::FromStr, sync::Arc};
+use std::{cmp::Reverse, collections::{HashMap, HashSet, VecDeque}, ffi::OsStr, path::{Path, PathBuf}, str::FromStr, sync::Arc};
 
 use async_recursion::async_recursion;
 use tokio::sync::RwLock;
-use walkdir::WalkDir;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use walkdir::{DirEntry, WalkDir};
 
-use crate::{convert::{dir::DirType, file::FileType}, util::{resolve_osstr, resolve_osstr_default, sem::Lock}};
+use crate::{convert::{dir::DirType, file::{DocumentLink, FileType}}, error::HeliumError, util::{resolve_osstr, resolve_osstr_default, sem::Lock}};
+
+/*
+ * incoming links of each document
+ */
+pub mod backlink;
 
 pub struct Node {
     pub property: NodeProperty,
@@ -70,6 +77,7 @@ pub struct NodeProperty {
     pub lk: Lock,
 }
 #[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
 pub enum NodeType {
     Dir(RwLock<Option<DirType>>),
     File(RwLock<Option<FileType>>),
@@ -82,8 +90,78 @@ pub struct NodePath {
     pub target_path: Option<PathBuf>,
 }
 
-pub async fn build_tree(path: &Path) -> Result<Arc<Node>, Box<dyn std::error::Error + Send + Sync>>{
-    let dir = WalkDir::new(path); 
+/*
+ * `ignore` patterns of the config file
+ */
+pub fn build_ignore_set(patterns: &[String]) -> Result<GlobSet, HeliumError> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern).map_err(|err| HeliumError::config(format!("invalid ignore pattern: {}", err)))?);
+    }
+    builder.build().map_err(|err| HeliumError::config(err.to_string()))
+}
+
+/*
+ * gitignore-style patterns of the `.heliumignore` file at the root of a node
+ */
+pub static IGNORE_FILE: &str = ".heliumignore";
+
+fn load_ignore_file(path: &Path) -> Result<Gitignore, HeliumError> {
+    let mut builder = GitignoreBuilder::new(path);
+    let ignore_file = path.join(IGNORE_FILE);
+    if ignore_file.is_file() {
+        if let Some(err) = builder.add(&ignore_file) {
+            return Err(HeliumError::config(err.to_string()).at(&ignore_file));
+        }
+    }
+    builder.build().map_err(|err| HeliumError::config(err.to_string()).at(&ignore_file))
+}
+
+fn is_hidden(entry: &DirEntry) -> bool {
+    entry.depth() > 0 && entry.file_name().to_str().is_some_and(|name| name.starts_with('.'))
+}
+
+/*
+ * key a link uses to reach the file, the stem of markdown files and the file name of everything else
+ */
+fn resource_key(path: &Path) -> Result<String, HeliumError> {
+    match resolve_osstr(path.extension())? {
+        "md" => Ok(resolve_osstr(path.file_stem())?.to_string()),
+        _ => Ok(resolve_osstr(path.file_name())?.to_string()),
+    }
+}
+
+/*
+ * tree of a node and the files left out of it by the ignore patterns, hidden entries like .obsidian are skipped without a trace
+ */
+pub async fn build_tree(path: &Path, ignore: &GlobSet) -> Result<(Arc<Node>, Vec<(String, PathBuf)>), HeliumError>{
+    let ignore_file = match path.is_dir() {
+        true => load_ignore_file(path)?,
+        false => Gitignore::empty(),
+    };
+    let is_ignored = |entry: &DirEntry| {
+        match entry.path().strip_prefix(path) {
+            Ok(rel) if !rel.as_os_str().is_empty() => {
+                ignore.is_match(rel) || ignore_file.matched(entry.path(), entry.file_type().is_dir()).is_ignore()
+            },
+            _ => false,
+        }
+    };
+
+    /*
+     * ignored directories are skipped with everything inside them, the node itself is always kept
+     */
+    let mut ignored = Vec::new();
+    let dir = WalkDir::new(path).into_iter().filter_entry(|entry| {
+        if is_hidden(entry) {
+            return false;
+        }
+        if is_ignored(entry) {
+            ignored.push(entry.path().to_path_buf());
+            return false;
+        }
+        true
+    }); 
     let mut node_stack: Vec<Arc<Node>> = Vec::new();
     let mut depth: usize = 0;
     let web_root = PathBuf::from_str("./")?;
@@ -134,7 +212,17 @@ pub async fn build_tree(path: &Path) -> Result<Arc<Node>, Box<dyn std::error::Er
         node_stack.push(node); 
     }
 
-    Ok(node_stack.first().ok_or("no node created")?.clone())
+    let mut ignored_files = Vec::new();
+    for path in ignored {
+        for entry in WalkDir::new(&path).into_iter().filter_entry(|entry| !is_hidden(entry)) {
+            let entry = entry?;
+            if entry.file_type().is_file() {
+                ignored_files.push((resource_key(entry.path())?, entry.path().to_path_buf()));
+            }
+        }
+    }
+
+    Ok((node_stack.first().ok_or("no node created")?.clone(), ignored_files))
 }
 
 
@@ -184,26 +272,98 @@ pub async fn flatten_file_node(root: &Arc<Node>) -> Vec<Arc<Node>> {
 
     nodes
 }
+/*
+ * directory entry holding the node, looking through page nodes created by page_children
+ */
+pub async fn find_parent(root: &Arc<Node>, node: &Arc<Node>) -> Option<Arc<Node>> {
+    for dir in flatten_dir_node(root).await {
+        if let NodeType::Dir(lk) = &dir.property.node_type {
+            if let Some(DirType::Page(_, _)) = &*lk.read().await {
+                continue;
+            }
+        }
+        for child in &*dir.children.read().await {
+            if Arc::ptr_eq(child, node) {
+                return Some(dir.clone());
+            }
+            if let NodeType::Dir(lk) = &child.property.node_type {
+                if let Some(DirType::Page(_, _)) = &*lk.read().await {
+                    if child.children.read().await.iter().any(|page_child| Arc::ptr_eq(page_child, node)) {
+                        return Some(dir.clone());
+                    }
+                }
+            }
+        }
+    }
+    None
+}
 
-pub async fn init_remaining_path(root: &Arc<Node>, target_prefix: &Path, collect_documents: &Option<&Path>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+/*
+ * documents sharing a file name would overwrite each other in the collect directory
+ * the first one by source path keeps the name, the others get a numbered suffix
+ */
+async fn collected_names(root: &Arc<Node>) -> Result<HashMap<PathBuf, String>, HeliumError> {
+    let mut documents = Vec::new();
+    for node in flatten_file_node(root).await {
+        if let NodeType::File(lk) = &node.property.node_type {
+            if let Some(FileType::Markdown(fp, _)) = &*lk.read().await {
+                documents.push((fp.key.clone(), node.path.read().await.org_path.clone()));
+            }
+        }
+    }
+    documents.sort();
+
+    let mut names = HashMap::new();
+    let mut used = HashSet::new();
+    let mut renamed = Vec::new();
+    for (idx, (key, org_path)) in documents.iter().enumerate() {
+        match idx > 0 && documents[idx - 1].0 == *key {
+            true => renamed.push((key, org_path)),
+            false => {
+                used.insert(format!("{}.html", key));
+                names.insert(org_path.clone(), format!("{}.html", key));
+            }
+        }
+    }
+    for (key, org_path) in renamed {
+        let mut n = 1;
+        while used.contains(&format!("{}-{}.html", key, n)) {
+            n += 1;
+        }
+        used.insert(format!("{}-{}.html", key, n));
+        names.insert(org_path.clone(), format!("{}-{}.html", key, n));
+    }
+
+    Ok(names)
+}
+
+pub async fn init_remaining_path(root: &Arc<Node>, target_prefix: &Path, collect_documents: &Option<&Path>) -> Result<(), HeliumError> {
 
     let web_root_prefix = PathBuf::from_str("/")?;
+    let names = match collect_documents {
+        Some(_) => collected_names(root).await?,
+        None => HashMap::new(),
+    };
 
     let mut queue = VecDeque::new();
     queue.push_back((root.to_owned(), PathBuf::new()));
     
     while let Some((node, mut path)) = queue.pop_front() {
-        path.push(resolve_osstr_default(node.path.read().await.rel_path.file_name())?); 
+        let mut path_lk = node.path.write().await;
+        let file_name = resolve_osstr_default(path_lk.rel_path.file_name()).map_err(|err| err.at(&path_lk.org_path))?.to_owned();
+        path.push(&file_name); 
         if let NodeType::File(lk) = &node.property.node_type {
             if let Some(FileType::Markdown(_, _)) = &*lk.read().await {
                 if let Some(collect_path) = collect_documents {
                     // File node has no children. So it's okay to modify path.
                     path = collect_path.to_path_buf();
-                    path.push(resolve_osstr_default(node.path.read().await.rel_path.file_name())?);
+                    match names.get(&path_lk.org_path) {
+                        Some(name) => path.push(name),
+                        None => path.push(&file_name),
+                    }
                 }
             }
         }
-        let mut path_lk = node.path.write().await;
         path_lk.abs_path = Some(web_root_prefix.join(&path));
         path_lk.target_path = Some(target_prefix.join(&path));
         for node in &*node.children.read().await {
@@ -216,13 +376,106 @@ pub async fn init_remaining_path(root: &Arc<Node>, target_prefix: &Path, collect
 
 
 
+/*
+ * linkable node and the path it is served from
+ */
+#[derive(Clone)]
+pub struct Resource {
+    pub org_path: PathBuf,
+    pub abs_path: PathBuf,
+    pub node: Arc<Node>,
+}
+
+/*
+ * resources by key, several files in different folders can share one key
+ */
+#[derive(Default)]
+pub struct ResourceMap {
+    resources: HashMap<String, Vec<Resource>>,
+    /*
+     * keys of drafts, links to them are rendered as plain text
+     */
+    excluded: HashSet<String>,
+    /*
+     * files left out by the ignore patterns, links to them are unresolved but rendered as plain text
+     */
+    ignored: HashMap<String, PathBuf>,
+}
+impl ResourceMap {
+    pub fn exclude(&mut self, key: String) {
+        self.excluded.insert(key);
+    }
+    pub fn is_excluded(&self, link: &DocumentLink) -> bool {
+        self.excluded.contains(link.key()) && self.candidates(link).is_empty()
+    }
+    pub fn ignore(&mut self, key: String, path: PathBuf) {
+        self.ignored.entry(key).or_insert(path);
+    }
+    pub fn ignored(&self, link: &DocumentLink) -> Option<&Path> {
+        match self.candidates(link).is_empty() {
+            true => self.ignored.get(link.key()).map(PathBuf::as_path),
+            false => None,
+        }
+    }
+    fn insert(&mut self, key: String, resource: Resource) {
+        let resources = self.resources.entry(key).or_default();
+        resources.push(resource);
+        resources.sort_by(|a, b| a.org_path.cmp(&b.org_path));
+    }
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Resource)> {
+        self.resources.iter().flat_map(|(key, resources)| resources.iter().map(move |resource| (key, resource)))
+    }
+    pub fn get(&self, key: &str) -> &[Resource] {
+        match self.resources.get(key) {
+            Some(resources) => resources,
+            None => &[],
+        }
+    }
+    pub fn duplicates(&self) -> impl Iterator<Item = (&String, &Vec<Resource>)> {
+        self.resources.iter().filter(|(_, resources)| resources.len() > 1)
+    }
+
+    /*
+     * resources a link can point to, [[folder/note]] only matches files inside a folder named folder
+     */
+    pub fn candidates(&self, link: &DocumentLink) -> Vec<&Resource> {
+        let folders: Vec<&str> = link.target.split('/')
+            .filter(|part| !part.is_empty() && *part != ".")
+            .collect();
+        let folders = &folders[..folders.len().saturating_sub(1)];
+        self.get(link.key()).iter()
+            .filter(|resource| {
+                let parents: Vec<&OsStr> = resource.org_path.parent()
+                    .map(|parent| parent.iter().collect())
+                    .unwrap_or_default();
+                parents.len() >= folders.len()
+                    && parents[parents.len() - folders.len()..].iter().zip(folders).all(|(parent, folder)| *parent == OsStr::new(folder))
+            })
+            .collect()
+    }
+
+    /*
+     * the way obsidian resolves links, among the candidates the file closest to the linking note wins
+     */
+    pub fn resolve(&self, link: &DocumentLink, from: &Path) -> Option<&Resource> {
+        let from = from.parent().unwrap_or(from);
+        self.candidates(link).into_iter()
+            .min_by_key(|resource| {
+                let parent = resource.org_path.parent().unwrap_or(&resource.org_path);
+                let common = parent.iter().zip(from.iter()).take_while(|(a, b)| a == b).count();
+                (Reverse(common), parent.iter().count(), resource.org_path.clone())
+            })
+    }
+}
+
 /*
  * collect all resources that can be linked to other resources
  */
-pub async fn collect_resource(root: &Arc<Node>) -> Result<HashMap<String, PathBuf>, Box<dyn std::error::Error + Send + Sync>> {
-    let mut resource_map = HashMap::new();
+pub async fn collect_resource(root: &Arc<Node>) -> Result<ResourceMap, HeliumError> {
+    let mut resource_map = ResourceMap::default();
     let file_nodes = flatten_file_node(root).await;
     for node in file_nodes {
+        let path_lk = node.path.read().await;
         let key = match &node.property.node_type {
             NodeType::File(lk) => {
                 let lk = lk.read().await;
@@ -234,21 +487,21 @@ pub async fn collect_resource(root: &Arc<Node>) -> Result<HashMap<String, PathBu
                         p.key.clone()
                     },
                     None => {
-                        return Err("collect resource panic".into());
+                        return Err(HeliumError::from("file is not prepared, cannot collect it as a resource").at(&path_lk.org_path));
                     }
                 }
                 
             },
             _ => {
-                return Err("collect reource panic: node type dir".into());
+                return Err(HeliumError::from("a directory cannot be collected as a resource").at(&path_lk.org_path));
             }
         };
-        match &node.path.read().await.abs_path {
+        match &path_lk.abs_path {
             Some(abs_path) => {
-                resource_map.insert(key, abs_path.to_owned());
+                resource_map.insert(key, Resource { org_path: path_lk.org_path.clone(), abs_path: abs_path.to_owned(), node: node.clone() });
             },
             None => {
-                return Err("abs_path not ready".into());
+                return Err(HeliumError::from("abs_path not ready").at(&path_lk.org_path));
             }
 
         }
@@ -0,0 +1,19 @@
+/*
+ * helium as a library, the binary is a thin command line over it
+ *
+ * let site = Site::builder().config("config.yaml").build().await?;
+ * let build = site.build().await?;
+ */
+pub mod build;
+pub mod cache;
+pub mod check;
+pub mod context;
+pub mod index;
+pub mod convert;
+pub mod error;
+pub mod serve;
+pub mod site;
+pub mod util;
+pub mod watch;
+
+pub use site::{Site, SiteBuilder};
@@ -0,0 +1,220 @@
+use std::{ffi::OsString, path::{Component, Path, PathBuf}};
+
+use tokio::{fs::{create_dir_all, read}, io::{AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader}, net::{TcpListener, TcpStream}, sync::watch};
+
+use crate::{error::HeliumError, site::Site, util::watch::Watcher, watch::rebuild};
+
+/*
+ * event stream endpoint used by the script injected in convert::render
+ */
+pub static LIVE_RELOAD_PATH: &str = "/__helium/livereload";
+
+enum Resolved {
+    File(PathBuf),
+    Redirect(String),
+    NotFound,
+}
+
+pub async fn serve(site: &Site, port: u16) -> Result<(), HeliumError> {
+    let context = site.context.clone();
+    create_dir_all(&context.target_base).await?;
+    let mut build = site.build().await?;
+
+    /*
+     * rebuild on every change of the vault and notify connected pages
+     */
+    let (tx, rx) = watch::channel(0usize);
+    let mut watcher = Watcher::new(&context.nodes).await?;
+    let site = site.clone();
+    tokio::spawn(async move {
+        let mut version = 0;
+        loop {
+            let changes = match watcher.changed().await {
+                Ok(changes) => changes,
+                Err(err) => {
+                    eprintln!("watch failed: {}", err);
+                    return;
+                }
+            };
+            for change in &changes {
+                println!("changed: {}", change.path().display());
+            }
+            match rebuild(&site, &mut build, &changes).await {
+                Ok(()) => {
+                    version += 1;
+                    let _ = tx.send(version);
+                },
+                Err(err) => {
+                    eprintln!("rebuild failed: {}", err);
+                }
+            }
+        }
+    });
+
+    let listener = TcpListener::bind(("127.0.0.1", port)).await?;
+    println!("serving {} at http://127.0.0.1:{}", context.target_base.display(), port);
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let rx = rx.clone();
+        let context = context.clone();
+        tokio::spawn(async move {
+            if let Err(err) = handle(stream, rx, &context.target_base).await {
+                eprintln!("request failed: {}", err);
+            }
+        });
+    }
+}
+
+async fn handle(stream: TcpStream, reload: watch::Receiver<usize>, base: &Path) -> Result<(), HeliumError> {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 || line.trim_end().is_empty() {
+            break;
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("");
+    let target = parts.next().unwrap_or("/");
+    let head = match method {
+        "GET" => false,
+        "HEAD" => true,
+        _ => {
+            return respond(&mut writer, "405 Method Not Allowed", &[("Content-Type", "text/plain")], b"method not allowed", false).await;
+        }
+    };
+    let path = target.split(['?', '#']).next().unwrap_or("/");
+
+    if path == LIVE_RELOAD_PATH {
+        return live_reload(&mut writer, reload).await;
+    }
+
+    match resolve(base, path) {
+        Resolved::File(file) => {
+            let body = read(&file).await?;
+            respond(&mut writer, "200 OK", &[("Content-Type", content_type(&file))], &body, head).await
+        },
+        Resolved::Redirect(location) => {
+            respond(&mut writer, "301 Moved Permanently", &[("Location", &location)], b"", head).await
+        },
+        Resolved::NotFound => {
+            let not_found = base.join("404.html");
+            match not_found.is_file() {
+                true => {
+                    let body = read(&not_found).await?;
+                    respond(&mut writer, "404 Not Found", &[("Content-Type", "text/html; charset=utf-8")], &body, head).await
+                },
+                false => {
+                    respond(&mut writer, "404 Not Found", &[("Content-Type", "text/plain")], b"404 not found", head).await
+                }
+            }
+        }
+    }
+}
+
+/*
+ * same lookup rules as github pages
+ * /a/b -> /a/b, /a/b.html, redirect to /a/b/ when it is a directory
+ * /a/b/ -> /a/b/index.html
+ */
+fn resolve(base: &Path, path: &str) -> Resolved {
+    let decoded = percent_decode(path);
+    let mut target = base.to_path_buf();
+    for component in Path::new(decoded.trim_start_matches('/')).components() {
+        match component {
+            Component::Normal(c) => target.push(c),
+            Component::CurDir => {},
+            _ => return Resolved::NotFound,
+        }
+    }
+
+    if target.is_file() {
+        return Resolved::File(target);
+    }
+    if target.is_dir() {
+        if !path.ends_with('/') {
+            return Resolved::Redirect(format!("{}/", path));
+        }
+        let index = target.join("index.html");
+        return match index.is_file() {
+            true => Resolved::File(index),
+            false => Resolved::NotFound,
+        };
+    }
+
+    let mut html: OsString = target.into_os_string();
+    html.push(".html");
+    let html = PathBuf::from(html);
+    match html.is_file() {
+        true => Resolved::File(html),
+        false => Resolved::NotFound,
+    }
+}
+
+async fn live_reload<W: AsyncWrite + Unpin>(writer: &mut W, mut reload: watch::Receiver<usize>) -> Result<(), HeliumError> {
+    writer.write_all(b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: keep-alive\r\n\r\n").await?;
+    writer.flush().await?;
+    reload.borrow_and_update();
+    while reload.changed().await.is_ok() {
+        writer.write_all(b"data: reload\n\n").await?;
+        writer.flush().await?;
+    }
+    Ok(())
+}
+
+async fn respond<W: AsyncWrite + Unpin>(writer: &mut W, status: &str, headers: &[(&str, &str)], body: &[u8], head: bool) -> Result<(), HeliumError> {
+    let mut response = format!("HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n", status, body.len());
+    for (key, value) in headers {
+        response.push_str(&format!("{}: {}\r\n", key, value));
+    }
+    response.push_str("\r\n");
+    writer.write_all(response.as_bytes()).await?;
+    if !head {
+        writer.write_all(body).await?;
+    }
+    writer.flush().await?;
+    Ok(())
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|ext| ext.to_str()).map(|ext| ext.to_lowercase()).as_deref() {
+        Some("html") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js") => "text/javascript; charset=utf-8",
+        Some("json") => "application/json",
+        Some("xml") => "application/xml",
+        Some("txt") => "text/plain; charset=utf-8",
+        Some("wasm") => "application/wasm",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("ico") => "image/x-icon",
+        Some("pdf") => "application/pdf",
+        _ => "application/octet-stream",
+    }
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        if bytes[idx] == b'%' && idx + 2 < bytes.len() {
+            if let Some(byte) = std::str::from_utf8(&bytes[idx+1..idx+3]).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                decoded.push(byte);
+                idx += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[idx]);
+        idx += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
@@ -0,0 +1,120 @@
+use std::{path::PathBuf, sync::Arc};
+
+use crate::{build::{build_site, Build}, context::Context, convert::{highlight::Highlighter, render::Template}, error::HeliumError, util::{fs::FileLimit, token::{create_tokenizer, Tokenizer}}};
+
+/*
+ * everything a build reads besides the vault, handed to every step of the pipeline
+ * sites share nothing, so several of them can be built in one process
+ */
+#[derive(Clone)]
+pub struct Site {
+    pub context: Arc<Context>,
+    pub template: Arc<Template>,
+    pub tokenizer: Arc<dyn Tokenizer>,
+    pub highlighter: Option<Arc<Highlighter>>,
+    pub file_limit: Arc<FileLimit>,
+}
+impl Site {
+    pub fn builder() -> SiteBuilder {
+        SiteBuilder::default()
+    }
+
+    /*
+     * render the whole site and publish it to target_base
+     */
+    pub async fn build(&self) -> Result<Build, HeliumError> {
+        build_site(self).await
+    }
+}
+
+/*
+ * the flags of the command line, applied on top of the config file
+ */
+#[derive(Default)]
+pub struct SiteBuilder {
+    config: Option<PathBuf>,
+    context: Option<Context>,
+    target: Option<PathBuf>,
+    tokenizer: Option<Arc<dyn Tokenizer>>,
+    drafts: bool,
+    keep_going: bool,
+    live_reload: bool,
+}
+impl SiteBuilder {
+    /*
+     * path of the config file, relative paths in it are resolved against the working directory
+     */
+    pub fn config(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config = Some(path.into());
+        self
+    }
+    /*
+     * an already loaded config, takes precedence over `config`
+     */
+    pub fn context(mut self, context: Context) -> Self {
+        self.context = Some(context);
+        self
+    }
+    /*
+     * overrides target_base of the config
+     */
+    pub fn target(mut self, target: impl Into<PathBuf>) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+    /*
+     * used instead of the configured tokenizer, tests build without python this way
+     */
+    pub fn tokenizer(mut self, tokenizer: Arc<dyn Tokenizer>) -> Self {
+        self.tokenizer = Some(tokenizer);
+        self
+    }
+    pub fn drafts(mut self, drafts: bool) -> Self {
+        self.drafts = drafts;
+        self
+    }
+    pub fn keep_going(mut self, keep_going: bool) -> Self {
+        self.keep_going = keep_going;
+        self
+    }
+    pub fn live_reload(mut self, live_reload: bool) -> Self {
+        self.live_reload = live_reload;
+        self
+    }
+
+    /*
+     * load and validate the config, templates, tokenizer and highlighter, any of them failing stops before the vault is read
+     */
+    pub async fn build(self) -> Result<Site, HeliumError> {
+        let mut context = match (self.context, self.config) {
+            (Some(context), _) => context,
+            (None, Some(path)) => Context::new(&path)?,
+            (None, None) => return Err(HeliumError::config("no config file given")),
+        };
+        if let Some(target) = self.target {
+            context.target_base = target;
+        }
+        context.drafts = self.drafts;
+        context.keep_going = self.keep_going;
+        context.live_reload = self.live_reload;
+        if let Some(err) = context.validate().into_iter().next() {
+            return Err(err);
+        }
+
+        let template = Template::new(&context)?;
+        let tokenizer = match self.tokenizer {
+            Some(tokenizer) => tokenizer,
+            None => create_tokenizer(&context.tokenizer).await?,
+        };
+        let highlighter = Highlighter::new(&context)?;
+        let file_limit = FileLimit::new(context.open_file_limit).await?;
+
+        Ok(Site {
+            context: Arc::new(context),
+            template: Arc::new(template),
+            tokenizer,
+            highlighter: highlighter.map(Arc::new),
+            file_limit: Arc::new(file_limit),
+        })
+    }
+}
@@ -1,58 +1,93 @@
-use std::{error::Error, collections::VecDeque, path::{Path, PathBuf}};
+use std::{collections::{HashSet, VecDeque}, path::{Path, PathBuf}};
 
-use tokio::{fs::{copy, create_dir_all, read_dir, remove_file, File}, io::{AsyncWriteExt, BufWriter}, sync::OnceCell};
+use tokio::{fs::{copy, create_dir_all, metadata, read, read_dir, remove_dir, remove_dir_all, remove_file, rename, File}, io::{AsyncWriteExt, BufWriter}, };
 use walkdir::{DirEntry, WalkDir};
 
-use crate::get_context;
+use crate::error::HeliumError;
 
 use super::{resolve_osstr, sem::Lock};
 
-struct FileLimit {
+/*
+ * bounds the files a build keeps open at once, shared by every task of a site
+ */
+pub struct FileLimit {
     lk: Lock
 }
-static FILE_LIMIT: OnceCell<FileLimit> = OnceCell::const_new();
-async fn get_file_limit() -> Result<&'static FileLimit, Box<dyn std::error::Error + Send + Sync>> {
-    let context = get_context().await;
-    let limit = context.open_file_limit.checked_sub(20).ok_or("open file limit should be bigger than 20")?;
-
-    let lk = Lock::new(&["file_desc_limit"]);
-    lk.ready_size("file_desc_limit", limit).await?;
-    Ok(FILE_LIMIT.get_or_init(|| async {
-        FileLimit {
+impl FileLimit {
+    pub async fn new(open_file_limit: usize) -> Result<Self, HeliumError> {
+        let limit = open_file_limit.checked_sub(20).ok_or_else(|| HeliumError::config("open_file_limit should be bigger than 20"))?;
+
+        let lk = Lock::new(&["file_desc_limit"]);
+        lk.ready_size("file_desc_limit", limit).await?;
+        Ok(FileLimit {
             lk,
-        } 
-    }).await)
+        })
+    }
+}
+
+/*
+ * files with the same bytes are left untouched so the git diff of the deployed site stays small
+ */
+async fn is_unchanged(target: &Path, b: &[u8]) -> bool {
+    match read(target).await {
+        Ok(existing) => existing == b,
+        Err(_) => false,
+    }
+}
+
+async fn is_same_file(from: &Path, to: &Path) -> Result<bool, HeliumError> {
+    if let (Ok(from_meta), Ok(to_meta)) = (metadata(from).await, metadata(to).await) {
+        if from_meta.len() == to_meta.len() && read(from).await? == read(to).await? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+async fn copy_if_changed(from: &Path, to: &Path) -> Result<(), HeliumError> {
+    if is_same_file(from, to).await? {
+        return Ok(());
+    }
+    copy(from, to).await.map_err(|err| HeliumError::from(err).at(from))?;
+    Ok(())
 }
 
-pub async fn write_from_string(target: &Path, s: String) -> Result<(), Box<dyn Error + Sync + Send>> {
+pub async fn write_from_string(limit: &FileLimit, target: &Path, s: String) -> Result<(), HeliumError> {
     /*
      * fs function must hold SemaphorePermit until it ends
      */
-    let _sem = get_file_limit().await?.lk.access("file_desc_limit").await?;
-    let f = File::options().write(true).create(true).open(&target).await?;
+    let _sem = limit.lk.access("file_desc_limit").await?;
+    if is_unchanged(target, s.as_bytes()).await {
+        return Ok(());
+    }
+    let f = File::options().write(true).create(true).truncate(true).open(&target).await.map_err(|err| HeliumError::from(err).at(target))?;
     let mut writer = BufWriter::new(f);
-    writer.write(s.as_bytes()).await?;
+    writer.write_all(s.as_bytes()).await?;
     writer.flush().await?;
     Ok(())
 }
 
-pub async fn write_from_slice(target: &Path, b: &[u8]) -> Result<(), Box<dyn Error + Sync + Send>> {
-    let f = File::options().write(true).create(true).truncate(true).open(&target).await?;
+pub async fn write_from_slice(limit: &FileLimit, target: &Path, b: &[u8]) -> Result<(), HeliumError> {
+    let _sem = limit.lk.access("file_desc_limit").await?;
+    if is_unchanged(target, b).await {
+        return Ok(());
+    }
+    let f = File::options().write(true).create(true).truncate(true).open(&target).await.map_err(|err| HeliumError::from(err).at(target))?;
     let mut writer = BufWriter::new(f);
-    writer.write(b).await?;
+    writer.write_all(b).await?;
     writer.flush().await?;
     Ok(())
 }
 
-pub async fn copy_file(from: &Path, to: &Path) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+pub async fn copy_file(limit: &FileLimit, from: &Path, to: &Path) -> Result<(), HeliumError> {
     /*
      * fs function must hold SemaphorePermit until it ends
      */
-    let _sem = get_file_limit().await?.lk.access("file_desc_limit").await?;
+    let _sem = limit.lk.access("file_desc_limit").await?;
     if from.is_dir() || to.is_dir() {
         return Err("not files".into());
     }
-    copy(from, to).await?;
+    copy_if_changed(from, to).await?;
     Ok(())
 }
 
@@ -63,32 +98,86 @@ fn is_hidden(entry: &DirEntry) -> bool {
          .unwrap_or(false)
 }
 
-pub async fn remove_dir(path: &Path, remove_hidden: bool) -> Result<(), Box<dyn std::error::Error + Send + Sync>> { 
-    /*
-     * fs function must hold SemaphorePermit until it ends
-     */
-    let _sem = get_file_limit().await?.lk.access("file_desc_limit").await?;
+/*
+ * a build is rendered into this directory next to target_base and published only when it finished
+ */
+pub fn staging_path(target: &Path) -> Result<PathBuf, HeliumError> {
+    let name = resolve_osstr(target.file_name())?;
+    Ok(target.with_file_name(format!(".{}.helium-staging", name)))
+}
 
-    let entries = WalkDir::new(path);
-    let entries = entries.into_iter().filter_entry(|e| { remove_hidden || !is_hidden(e) });
-    for entry in entries {
+pub async fn reset_staging(staging: &Path) -> Result<(), HeliumError> {
+    if staging.exists() {
+        remove_dir_all(staging).await.map_err(|err| HeliumError::from(err).at(staging))?;
+    }
+    create_dir_all(staging).await?;
+    Ok(())
+}
+
+/*
+ * entries of target_base that were not generated, like the .git of a pages checkout or its CNAME
+ */
+fn is_kept(entry: &DirEntry) -> bool {
+    is_hidden(entry) || (entry.depth() == 1 && entry.file_name() == "CNAME")
+}
+
+/*
+ * move the files of a finished build into target, files with the same bytes are left untouched
+ * so their mtimes and the git diff of the deployed site stay the same
+ * everything else under target the build did not produce is removed, except the kept entries
+ */
+pub async fn publish_staging(staging: &Path, target: &Path) -> Result<(), HeliumError> {
+    create_dir_all(target).await?;
+
+    let mut published = HashSet::new();
+    for entry in WalkDir::new(staging).min_depth(1) {
         let entry = entry?;
-        if entry.depth() == 1 {
-            if entry.path().is_dir() {
-                tokio::fs::remove_dir_all(entry.path()).await?;
-            } else {
-                remove_file(entry.path()).await?;
-            }
+        let rel = entry.path().strip_prefix(staging)?.to_path_buf();
+        let to = target.join(&rel);
+        match entry.file_type().is_dir() {
+            true => {
+                if to.is_file() {
+                    remove_file(&to).await?;
+                }
+                create_dir_all(&to).await?;
+            },
+            false => {
+                if to.is_dir() {
+                    remove_dir_all(&to).await?;
+                }
+                if !is_same_file(entry.path(), &to).await? {
+                    rename(entry.path(), &to).await.map_err(|err| HeliumError::from(err).at(&to))?;
+                }
+            },
+        }
+        published.insert(rel);
+    }
+
+    let entries = WalkDir::new(target).min_depth(1).contents_first(true);
+    for entry in entries.into_iter().filter_entry(|entry| !is_kept(entry)) {
+        let entry = entry?;
+        if published.contains(entry.path().strip_prefix(target)?) {
+            continue;
+        }
+        match entry.file_type().is_dir() {
+            true => {
+                if read_dir(entry.path()).await?.next_entry().await?.is_none() {
+                    remove_dir(entry.path()).await?;
+                }
+            },
+            false => remove_file(entry.path()).await?,
         }
     }
+
+    remove_dir_all(staging).await?;
     Ok(())
 }
 
-pub async fn copy_recursive(from: &Path, to: &Path, copy_dotfile: bool) -> Result<(), Box<dyn Error + Sync + Send>> {
+pub async fn copy_recursive(limit: &FileLimit, from: &Path, to: &Path, copy_dotfile: bool) -> Result<(), HeliumError> {
     /*
      * fs function must hold SemaphorePermit until it ends
      */
-    let _sem = get_file_limit().await?.lk.access("file_desc_limit").await?;
+    let _sem = limit.lk.access("file_desc_limit").await?;
 
     let from: PathBuf = from.into();
     let mut to: PathBuf = to.into();
@@ -112,7 +201,7 @@ pub async fn copy_recursive(from: &Path, to: &Path, copy_dotfile: bool) -> Resul
             },
             false => {
                 if copy_dotfile || !cursor.file_name().ok_or("no file name")?.to_str().ok_or("cannot convert to str")?.starts_with('.') {
-                    copy(&cursor, &target).await?;
+                    copy_if_changed(&cursor, &target).await?;
                 }
             }
         }
@@ -1,20 +1,39 @@
 use std::{ffi::OsStr, path::Path};
 
+use crate::error::HeliumError;
+
 pub mod fs;
 pub mod sem;
 pub mod token;
+pub mod watch;
 
-pub fn resolve_osstr(osstr: Option<&OsStr>) -> Result<&str, Box<dyn std::error::Error + Send + Sync>> {
-    Ok(osstr.ok_or("resolve osstr failed")?.to_str().ok_or("osstr to str error")?)
+pub fn resolve_osstr(osstr: Option<&OsStr>) -> Result<&str, HeliumError> {
+    Ok(osstr.ok_or("path has no file name")?.to_str().ok_or("path is not valid utf-8")?)
 }
-pub fn resolve_osstr_default(osstr: Option<&OsStr>) -> Result<&str, Box<dyn std::error::Error + Send + Sync>> {
+pub fn resolve_osstr_default(osstr: Option<&OsStr>) -> Result<&str, HeliumError> {
     match osstr {
-        Some(osstr) => Ok(osstr.to_str().ok_or("osstr to str error")?),
+        Some(osstr) => Ok(osstr.to_str().ok_or("path is not valid utf-8")?),
         None => Ok(""),
     }
 }
 
-pub fn resolve_path(path: &Path) -> Result<&str, Box<dyn std::error::Error + Send + Sync>> {
-    Ok(path.to_str().ok_or("resolve path failed")?)
+pub fn resolve_path(path: &Path) -> Result<&str, HeliumError> {
+    Ok(path.to_str().ok_or("path is not valid utf-8")?)
+}
+
+/*
+ * a served path as it is written in links, feeds and the sitemap
+ * every byte a path segment may not hold in a uri is percent-encoded, `/` separates the segments
+ */
+pub fn encode_path(path: &Path) -> Result<String, HeliumError> {
+    let mut encoded = String::new();
+    for byte in resolve_path(path)?.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' |
+                b'!' | b'$' | b'&' | b'\'' | b'(' | b')' | b'*' | b'+' | b',' | b';' | b'=' | b':' | b'@' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    Ok(encoded)
 }
 
@@ -2,6 +2,8 @@ use std::collections::HashMap;
 
 use tokio::sync::{Semaphore, SemaphorePermit};
 
+use crate::error::HeliumError;
+
 #[derive(Debug)]
 pub struct Lock {
     lk: HashMap<String, Semaphore>,
@@ -17,20 +19,25 @@ impl Lock {
             lk
         }
     }
-    pub async fn ready(&self, key: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        self.lk.get(&key.to_string())
-            .ok_or("lock key does not exist")?
-            .add_permits(Semaphore::MAX_PERMITS);
+    pub async fn ready(&self, key: &str) -> Result<(), HeliumError> {
+        let sem = self.lk.get(key)
+            .ok_or("lock key does not exist")?;
+        /*
+         * nodes are prepared and rendered again in watch mode, a ready lock stays ready
+         */
+        if sem.available_permits() == 0 {
+            sem.add_permits(Semaphore::MAX_PERMITS);
+        }
         Ok(())
     }
-    pub async fn ready_size(&self, key: &str, limit: usize) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        self.lk.get(&key.to_string())
+    pub async fn ready_size(&self, key: &str, limit: usize) -> Result<(), HeliumError> {
+        self.lk.get(key)
             .ok_or("lock key does not exist")?
             .add_permits(limit);
         Ok(())
     }
-    pub async fn access(&self, key: &str) -> Result<SemaphorePermit, Box<dyn std::error::Error + Send + Sync>> {
-        let sem = self.lk.get(&key.to_string())
+    pub async fn access(&self, key: &str) -> Result<SemaphorePermit<'_>, HeliumError> {
+        let sem = self.lk.get(key)
             .ok_or("lock key does not exist")?
             .acquire()
             .await?; 
@@ -1,16 +1,167 @@
-use std::{collections::{BTreeSet, VecDeque}, error::Error, fs::File, io::{BufWriter, Write}, process::Stdio, sync::Arc};
+use std::{collections::{HashSet, VecDeque}, process::Stdio, sync::Arc};
 
-use tokio::{sync::Mutex, process::{Command, Child}, io::{AsyncWriteExt, BufReader, AsyncBufReadExt}};
+use async_trait::async_trait;
+use tokio::{sync::{Mutex, Semaphore}, process::{Command, Child}, io::{AsyncWriteExt, BufReader, AsyncBufReadExt}};
 use serde::Deserialize;
+use unicode_segmentation::UnicodeSegmentation;
 
-use tokio::sync::OnceCell;
+use crate::{context::{TokenizerContext, TokenizerKind}, error::HeliumError};
 
-static TOKENIZER: OnceCell<Tokenizer> = OnceCell::const_new();
-pub async fn get_tokenizer() -> Result<&'static Tokenizer, Box<dyn std::error::Error + Send + Sync>> {
-    Ok(TOKENIZER.get_or_init(|| async {
-        
-        Tokenizer::new(5).unwrap()
-    }).await)
+/*
+ * start the configured backend, it is shared by every task of a site
+ */
+pub async fn create_tokenizer(context: &TokenizerContext) -> Result<Arc<dyn Tokenizer>, HeliumError> {
+    let tokenizer: Arc<dyn Tokenizer> = match context.kind {
+        TokenizerKind::Builtin => Arc::new(BuiltinTokenizer::new(context)?),
+        TokenizerKind::Kiwi => Arc::new(KiwiTokenizer::new(context)?),
+    };
+    /*
+     * fail before the build starts when the backend is not usable
+     */
+    tokenizer.tokenize("helium").await?;
+    Ok(tokenizer)
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Token {
+    pub data: Vec<String>,
+}
+
+/*
+ * splits text into the tokens stored in the search index
+ */
+#[async_trait]
+pub trait Tokenizer: Send + Sync {
+    /*
+     * identifies the produced tokens, documents cached with another tokenizer are parsed again
+     */
+    fn id(&self) -> String;
+    fn stop_words(&self) -> &HashSet<String>;
+    async fn tokenize(&self, s: &str) -> Result<Token, HeliumError>;
+}
+
+/*
+ * lists of stopwords-iso bundled with the stop-words crate, stop_words::get panics on any other code
+ */
+pub static STOP_WORD_LANGUAGES: &[&str] = &[
+    "af", "ar", "bg", "bn", "br", "ca", "cs", "da", "de", "el", "en", "eo", "es", "et", "eu", "fa", "fi", "fr", "ga", "gl",
+    "gu", "ha", "he", "hi", "hr", "hu", "hy", "id", "it", "ja", "ko", "ku", "la", "lt", "lv", "mr", "ms", "nl", "no", "pl",
+    "pt", "ro", "ru", "sk", "sl", "so", "st", "sv", "sw", "th", "tl", "tr", "uk", "ur", "vi", "yo", "zh", "zu",
+];
+
+fn load_stop_words(languages: &[String]) -> Result<HashSet<String>, HeliumError> {
+    let mut stop_words = HashSet::new();
+    for language in languages {
+        match STOP_WORD_LANGUAGES.contains(&language.as_str()) {
+            true => stop_words.extend(stop_words::get(language.as_str())),
+            false => return Err(HeliumError::config(format!("unknown stop word list `{}`", language))),
+        }
+    }
+    Ok(stop_words)
+}
+
+/*
+ * pure rust tokenizer, unicode word segmentation with korean particle stripping and n-grams
+ */
+pub struct BuiltinTokenizer {
+    languages: Vec<String>,
+    stop_words: HashSet<String>,
+}
+
+/*
+ * josa and common endings, the longest match is stripped from a hangul word
+ */
+static KOREAN_SUFFIXES: &[&str] = &[
+    "에서부터", "으로부터", "했습니다", "입니다만",
+    "에게서", "한테서", "이라고", "이라는", "으로서", "으로써", "에서는", "에게는", "합니다", "됩니다", "입니다", "했다는",
+    "까지", "부터", "에서", "에게", "한테", "으로", "로서", "로써", "처럼", "보다", "이나", "이랑", "라고", "라는", "하는", "하여", "해서", "하고", "하다", "했다", "되는", "된다", "이다", "였다", "에는", "과는", "와는", "으로는", "로는",
+    "은", "는", "이", "가", "을", "를", "에", "의", "와", "과", "도", "만", "로", "랑", "께",
+];
+const MAX_NGRAM: usize = 3;
+
+fn is_hangul(c: char) -> bool {
+    matches!(c, '\u{AC00}'..='\u{D7A3}' | '\u{1100}'..='\u{11FF}' | '\u{3130}'..='\u{318F}')
+}
+
+impl BuiltinTokenizer {
+    pub fn new(context: &TokenizerContext) -> Result<Self, HeliumError> {
+        Ok(BuiltinTokenizer {
+            languages: context.stop_words.clone(),
+            stop_words: load_stop_words(&context.stop_words)?,
+        })
+    }
+
+    fn hangul_tokens(&self, word: &str, tokens: &mut Vec<String>) {
+        let chars: Vec<char> = word.chars().collect();
+        if chars.len() >= 2 {
+            tokens.push(word.to_string());
+        }
+
+        let stem = KOREAN_SUFFIXES.iter()
+            .filter(|suffix| word.ends_with(*suffix) && word.len() > suffix.len())
+            .max_by_key(|suffix| suffix.len())
+            .map(|suffix| &word[..word.len() - suffix.len()])
+            .unwrap_or(word);
+        let stem: Vec<char> = stem.chars().collect();
+        if stem.len() >= 2 && stem.len() != chars.len() {
+            tokens.push(stem.iter().collect());
+        }
+
+        /*
+         * compound nouns are written without spaces, n-grams let a part of them match
+         */
+        if stem.len() > 2 {
+            for n in 2..=MAX_NGRAM.min(stem.len() - 1) {
+                for window in stem.windows(n) {
+                    tokens.push(window.iter().collect());
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Tokenizer for BuiltinTokenizer {
+    fn id(&self) -> String {
+        format!("builtin:{}", self.languages.join(","))
+    }
+    fn stop_words(&self) -> &HashSet<String> {
+        &self.stop_words
+    }
+    async fn tokenize(&self, s: &str) -> Result<Token, HeliumError> {
+        let mut tokens = Vec::new();
+        for word in s.unicode_words() {
+            /*
+             * split runs of hangul and other scripts, "Rust의" becomes "Rust" and "의"
+             */
+            let mut start = 0;
+            let mut chars = word.char_indices().peekable();
+            while let Some((idx, c)) = chars.next() {
+                let boundary = match chars.peek() {
+                    Some((_, next)) => is_hangul(c) != is_hangul(*next),
+                    None => true,
+                };
+                if !boundary {
+                    continue;
+                }
+                let end = idx + c.len_utf8();
+                let part = &word[start..end];
+                start = end;
+                match is_hangul(c) {
+                    true => self.hangul_tokens(part, &mut tokens),
+                    false => {
+                        if part.chars().count() >= 2 && part.chars().any(|c| c.is_alphabetic()) {
+                            tokens.push(part.to_string());
+                        }
+                    }
+                }
+            }
+        }
+        tokens.retain(|token| !self.stop_words.contains(token) && !self.stop_words.contains(&token.to_lowercase()));
+        tokens.dedup();
+
+        Ok(Token { data: tokens })
+    }
 }
 
 static MAIN_PY: &str = r#"
@@ -38,95 +189,80 @@ if __name__ == "__main__":
     main()
 "#;
 
-#[derive(Deserialize, Debug)]
-pub struct Token {
-    pub data: Vec<String>,
-}
-
 pub struct ModuleQueue{
     ready: VecDeque<Child>,
 }
-pub struct Tokenizer {
+/*
+ * kiwipiepy running in python3 worker processes, requires `pip install kiwipiepy`
+ * the workers are spawned with kill_on_drop, dropping the tokenizer ends them
+ */
+pub struct KiwiTokenizer {
     queues: Arc<Mutex<ModuleQueue>>,
-    pub all_tokens: Arc<Mutex<BTreeSet<String>>>,
-}
-impl Drop for Tokenizer {
-    fn drop(&mut self) {
-        let queues = self.queues.clone();
-        tokio::spawn(async move {
-            let mut queues = queues.lock().await;  
-            while let Some(mut module) = queues.ready.pop_front() {
-                module.kill().await.unwrap();
-            }
-        });
-    }
+    available: Semaphore,
+    languages: Vec<String>,
+    stop_words: HashSet<String>,
 }
-impl Tokenizer {
-    pub fn new(n: usize) -> Result<Self, Box<dyn Error + Send + Sync>> {
-        let f = File::options().write(true).create(true).truncate(true).open("./main.py")?;
-        let mut writer = BufWriter::new(f);
-        writer.write_all(MAIN_PY.as_bytes())?;
-        writer.flush()?;
-
+impl KiwiTokenizer {
+    pub fn new(context: &TokenizerContext) -> Result<Self, HeliumError> {
+        let stop_words = load_stop_words(&context.stop_words)?;
         let mut ready = VecDeque::new();
-        for _ in 0..n {
+        for _ in 0..context.workers {
             ready.push_back(
                 Command::new("python3")
-                .args(["./main.py"])
+                .args(["-c", MAIN_PY])
                 .stdin(Stdio::piped())
                 .stdout(Stdio::piped())
-                .spawn()?
+                .kill_on_drop(true)
+                .spawn()
+                .map_err(|err| HeliumError::tokenizer(format!("cannot start python3 workers ({})", err)))?
                 );
         }
 
-        Ok(Tokenizer {
+        Ok(KiwiTokenizer {
             queues: Arc::new(Mutex::new(ModuleQueue { ready })),
-            all_tokens: Arc::new(Mutex::new(BTreeSet::new())),
+            available: Semaphore::new(context.workers),
+            languages: context.stop_words.clone(),
+            stop_words,
         })
     }
+}
 
-    pub async fn tokenize(&self, s: &str) -> Result<Token, Box<dyn Error + Send + Sync>> {
-        loop {
-            let mut queues = self.queues.lock().await; 
-            match queues.ready.pop_front() {
-                Some(mut module) => {
-                    drop(queues);
-                    match &mut module.stdin {
-                        Some(input) => {
-                            input.write_all(format!("{}\n", s).as_bytes()).await?;
-                            input.flush().await?;
-                        },
-                        None => {
-                            dbg!("no stdin");
-                        }
-                    }
-                    let mut output = String::new();
-                    match &mut module.stdout {
-                        Some(out) => {
-                            let mut reader = BufReader::new(out);
-                            reader.read_line(&mut output).await?;
-                        },
-                        None => {
-                            dbg!("no stdout");
-                        }
-                    }
+#[async_trait]
+impl Tokenizer for KiwiTokenizer {
+    fn id(&self) -> String {
+        format!("kiwi:{}", self.languages.join(","))
+    }
+    fn stop_words(&self) -> &HashSet<String> {
+        &self.stop_words
+    }
+    async fn tokenize(&self, s: &str) -> Result<Token, HeliumError> {
+        /*
+         * wait for an idle worker instead of spinning on the queue
+         */
+        let _permit = self.available.acquire().await?;
+        let mut module = self.queues.lock().await.ready.pop_front().ok_or("no idle tokenizer worker")?;
 
-                    let mut queues = self.queues.lock().await; 
-                    queues.ready.push_back(module);
-                    drop(queues);
-
-                    let token: Token = serde_json::from_str(&output)?;
-                    
-                    /* let mut all_tokens = self.all_tokens.lock().await;
-                    all_tokens.extend(token.data.clone()); */
-                    
-                    return Ok(token);
-                },
-                None => {
-                    drop(queues);
-                }
-            }
-        }
+        /*
+         * lines are the protocol unit, the worker answers one json line per input line
+         */
+        let line = s.replace(['\n', '\r'], " ");
+        let result: Result<String, HeliumError> = async {
+            let input = module.stdin.as_mut().ok_or("no stdin")?;
+            input.write_all(format!("{}\n", line).as_bytes()).await?;
+            input.flush().await?;
+
+            let mut output = String::new();
+            let out = module.stdout.as_mut().ok_or("no stdout")?;
+            let mut reader = BufReader::new(out);
+            reader.read_line(&mut output).await?;
+            Ok(output)
+        }.await;
+        self.queues.lock().await.ready.push_back(module);
+
+        let output = result.map_err(|err| HeliumError::tokenizer(format!("kiwi worker failed ({})", err.message())))?;
+        let mut token: Token = serde_json::from_str(&output).map_err(|err| HeliumError::tokenizer(format!("kiwi tokenizer failed, is kiwipiepy installed? ({})", err)))?;
+        token.data.retain(|token| !self.stop_words.contains(token));
+
+        Ok(token)
     }
 }
-
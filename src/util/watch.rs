@@ -0,0 +1,115 @@
+use std::{path::{Component, Path, PathBuf}, time::Duration};
+
+use notify::{event::ModifyKind, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher as _};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+use crate::{error::HeliumError, index::IGNORE_FILE};
+
+#[derive(Debug, Clone)]
+pub enum Change {
+    Created(PathBuf),
+    Modified(PathBuf),
+    Removed(PathBuf),
+}
+impl Change {
+    pub fn path(&self) -> &Path {
+        match self {
+            Change::Created(path) | Change::Modified(path) | Change::Removed(path) => path,
+        }
+    }
+}
+
+/*
+ * filesystem notifications over the configured node paths
+ * dot entries are skipped because obsidian rewrites .obsidian/ on every keystroke, except the ignore file which changes the tree
+ */
+pub struct Watcher {
+    paths: Vec<PathBuf>,
+    events: UnboundedReceiver<notify::Result<Event>>,
+    /*
+     * notifications stop when the watcher is dropped
+     */
+    _watcher: RecommendedWatcher,
+    /*
+     * an editor saving a note sends several events, they are collected into one rebuild
+     */
+    settle: Duration,
+}
+impl Watcher {
+    pub async fn new(paths: &[PathBuf]) -> Result<Self, HeliumError> {
+        let (tx, events) = unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            let _ = tx.send(event);
+        })?;
+        for path in paths {
+            watcher.watch(path, RecursiveMode::Recursive).map_err(|err| HeliumError::from(err).at(path))?;
+        }
+        Ok(Watcher {
+            paths: paths.to_vec(),
+            events,
+            _watcher: watcher,
+            settle: Duration::from_millis(100),
+        })
+    }
+
+    /*
+     * wait until something under the watched paths changes
+     */
+    pub async fn changed(&mut self) -> Result<Vec<Change>, HeliumError> {
+        loop {
+            let mut changes = Vec::new();
+            let event = self.events.recv().await.ok_or("file watcher stopped")?;
+            self.push_changes(event?, &mut changes);
+            while let Ok(Some(event)) = tokio::time::timeout(self.settle, self.events.recv()).await {
+                self.push_changes(event?, &mut changes);
+            }
+
+            if !changes.is_empty() {
+                return Ok(changes);
+            }
+        }
+    }
+
+    fn push_changes(&self, event: Event, changes: &mut Vec<Change>) {
+        for path in event.paths {
+            if self.is_hidden(&path) {
+                continue;
+            }
+            let change = match event.kind {
+                EventKind::Create(_) => Change::Created(path),
+                EventKind::Remove(_) => Change::Removed(path),
+                /*
+                 * a rename is seen from both of its paths, the one that still exists was created
+                 */
+                EventKind::Modify(ModifyKind::Name(_)) => match path.exists() {
+                    true => Change::Created(path),
+                    false => Change::Removed(path),
+                },
+                EventKind::Modify(_) => Change::Modified(path),
+                _ => continue,
+            };
+            /*
+             * a file written several times is reported once, a created or removed one stays so
+             */
+            match changes.iter().position(|known| known.path() == change.path()) {
+                Some(idx) => {
+                    if let Change::Modified(_) = changes[idx] {
+                        changes[idx] = change;
+                    }
+                },
+                None => changes.push(change),
+            }
+        }
+    }
+
+    fn is_hidden(&self, path: &Path) -> bool {
+        let rel = match self.paths.iter().find_map(|root| path.strip_prefix(root).ok()) {
+            Some(rel) => rel,
+            None => return false,
+        };
+        rel.components().any(|component| match component {
+            Component::Normal(name) => name != IGNORE_FILE && name.to_str().is_some_and(|name| name.starts_with('.')),
+            _ => false,
+        })
+    }
+}
@@ -0,0 +1,198 @@
+use std::{collections::{HashMap, HashSet}, path::PathBuf, sync::Arc, time::Instant};
+
+use tokio::fs::create_dir_all;
+
+use crate::{build::{collect_documents_path, write_search_index, Build}, check::check_site, convert::{dir::{unpage_children, DirType}, feed::render_feeds, file::FileType, prepare_node, render_node, sitemap::render_sitemap, tag::render_tags}, error::HeliumError, index::{backlink::collect_backlink, find_parent, flatten_file_node, flatten_node, init_remaining_path, Node, NodeType}, site::Site, util::watch::{Change, Watcher}};
+
+pub async fn watch(site: &Site) -> Result<(), HeliumError> {
+    let context = &site.context;
+    let mut build = site.build().await?;
+
+    let mut watcher = Watcher::new(&context.nodes).await?;
+    println!("watching {} node path(s)", context.nodes.len());
+    loop {
+        let changes = watcher.changed().await?;
+        for change in &changes {
+            println!("changed: {}", change.path().display());
+        }
+        if let Err(err) = rebuild(site, &mut build, &changes).await {
+            eprintln!("rebuild failed: {}", err);
+        }
+    }
+}
+
+/*
+ * apply vault changes to an already built site
+ * created or removed files change the tree itself, so they fall back to a full build
+ */
+pub async fn rebuild(site: &Site, build: &mut Build, changes: &[Change]) -> Result<(), HeliumError> {
+    if changes.iter().any(|change| !matches!(change, Change::Modified(_))) {
+        *build = site.build().await?;
+        return Ok(());
+    }
+
+    let start_time = Instant::now();
+    let mut changed = Vec::new();
+    for node in flatten_file_node(&build.root).await {
+        let org_path = node.path.read().await.org_path.clone();
+        /*
+         * modified directories are skipped, their entries are reported on their own
+         */
+        if changes.iter().any(|change| change.path() == org_path) {
+            changed.push(node);
+        }
+    }
+
+    /*
+     * drafts and ignored files are not in the tree, a modified one may have been published
+     */
+    if changed.len() < changes.iter().filter(|change| change.path().is_file()).count() {
+        *build = site.build().await?;
+        return Ok(());
+    }
+
+    for node in &changed {
+        if !rebuild_file_node(site, build, node).await? {
+            *build = site.build().await?;
+            return Ok(());
+        }
+    }
+    write_search_index(site, &build.root, &build.target).await?;
+    render_tags(site, build).await?;
+    render_feeds(site, build).await?;
+    render_sitemap(site, build).await?;
+    build.report = check_site(build).await?;
+    build.report.print_summary();
+
+    println!("rebuilt {} node(s): {:?}", changed.len(), start_time.elapsed());
+    Ok(())
+}
+
+/*
+ * re-prepare one file node and re-render everything that shows its data:
+ * the node itself, the listing and pages of its parent, the documents linking to it
+ * and the documents it links to or linked to before, whose backlinks changed
+ * returns false when the document became a draft and has to leave the tree, or when paging the parent again
+ * moved a node to another page and so changed its url, both need a full build
+ */
+async fn rebuild_file_node(site: &Site, build: &mut Build, node: &Arc<Node>) -> Result<bool, HeliumError> {
+    let context = &site.context;
+    let mut link_targets = linked_keys(node).await;
+    prepare_node(site, node).await?;
+    link_targets.extend(linked_keys(node).await);
+    build.backlink_map = Arc::new(collect_backlink(&build.resource_map).await?);
+
+    let key = match &node.property.node_type {
+        NodeType::File(lk) => {
+            match &*lk.read().await {
+                Some(FileType::Markdown(_, doc)) if doc.is_excluded(context) => return Ok(false),
+                Some(FileType::Markdown(fp, _)) => Some(fp.key.clone()),
+                Some(FileType::Binary(_)) => None,
+                None => return Err("File not ready".into()),
+            }
+        },
+        NodeType::Dir(_) => return Err("rebuild panic: node type dir".into()),
+    };
+    /*
+     * binary files only need to be copied again
+     */
+    let key = match key {
+        Some(key) => key,
+        None => {
+            render_node(site, node, build.resource_map.clone(), build.backlink_map.clone()).await?;
+            return Ok(true);
+        }
+    };
+
+    /*
+     * the modified document can move inside its parent listing, so sort and page the parent again
+     */
+    let parent = find_parent(&build.root, node).await.ok_or("parent of changed node not found")?;
+    let before = served_paths(&build.root).await;
+    unpage_children(&parent).await?;
+    prepare_node(site, &parent).await?;
+    init_remaining_path(&build.root, &context.target_base, &collect_documents_path(context.render.collect_documents)).await?;
+    let served: HashMap<usize, &Option<PathBuf>> = before.iter()
+        .map(|(node, abs_path)| (Arc::as_ptr(node) as usize, abs_path))
+        .collect();
+    for (node, abs_path) in served_paths(&build.root).await {
+        if served.get(&(Arc::as_ptr(&node) as usize)).is_some_and(|served| **served != abs_path) {
+            return Ok(false);
+        }
+    }
+
+    render_node(site, node, build.resource_map.clone(), build.backlink_map.clone()).await?;
+    render_node(site, &parent, build.resource_map.clone(), build.backlink_map.clone()).await?;
+    for child in &*parent.children.read().await {
+        if let NodeType::Dir(lk) = &child.property.node_type {
+            if let Some(DirType::Page(_, _)) = &*lk.read().await {
+                if let Some(target_path) = &child.path.read().await.target_path {
+                    create_dir_all(target_path).await?;
+                }
+                render_node(site, child, build.resource_map.clone(), build.backlink_map.clone()).await?;
+            }
+        }
+    }
+
+    for linker in flatten_file_node(&build.root).await {
+        if Arc::ptr_eq(&linker, node) {
+            continue;
+        }
+        let links_to_node = match &linker.property.node_type {
+            NodeType::File(lk) => {
+                match &*lk.read().await {
+                    Some(FileType::Markdown(_, doc)) => {
+                        doc.parameter.link.iter().any(|(_, _, link)| link.link().key() == key)
+                    },
+                    _ => false,
+                }
+            },
+            _ => false,
+        };
+        if links_to_node {
+            render_node(site, &linker, build.resource_map.clone(), build.backlink_map.clone()).await?;
+        }
+    }
+
+    for target in link_targets {
+        for resource in build.resource_map.get(&target) {
+            if !Arc::ptr_eq(&resource.node, node) {
+                render_node(site, &resource.node, build.resource_map.clone(), build.backlink_map.clone()).await?;
+            }
+        }
+    }
+
+    Ok(true)
+}
+
+/*
+ * the url of every node, holding the nodes keeps the replaced page nodes from sharing an address with new ones
+ */
+async fn served_paths(root: &Arc<Node>) -> Vec<(Arc<Node>, Option<PathBuf>)> {
+    let mut served = Vec::new();
+    for node in flatten_node(root).await {
+        let abs_path = node.path.read().await.abs_path.clone();
+        served.push((node, abs_path));
+    }
+    served
+}
+
+/*
+ * keys of the resources a document links to
+ */
+async fn linked_keys(node: &Arc<Node>) -> HashSet<String> {
+    match &node.property.node_type {
+        NodeType::File(lk) => {
+            match &*lk.read().await {
+                Some(FileType::Markdown(_, doc)) => {
+                    doc.parameter.link.iter()
+                        .map(|(_, _, link)| link.link().key().to_string())
+                        .filter(|key| !key.is_empty())
+                        .collect()
+                },
+                _ => HashSet::new(),
+            }
+        },
+        NodeType::Dir(_) => HashSet::new(),
+    }
+}
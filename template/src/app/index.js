@@ -4,7 +4,10 @@ let Index;
 
 // external module should be re-called every page switch
 async function load_external_modules(){
-    // load highlight module    
+    // load highlight module, code is already highlighted when the site is built with render.highlight
+    if (!window.hljs) {
+        return;
+    }
     window.hljs.highlightAll();
     window.hljs.initLineNumbersOnLoad();
 }
@@ -73,24 +76,8 @@ async function load_toc_module(){
     */
 
 
-    // create toc elements
+    // the toc is rendered with the page, headings and its entries only scroll smoothly
     const headings = document.querySelectorAll('h1, h2, h3, h4, h5, h6');
-    const toc = document.getElementById('toc');
-    // reset toc
-    while (toc.firstChild) {
-        toc.removeChild(toc.lastChild);
-    }
-
-    const root = document.createElement('ul');
-    const stk = [{layer: 0, elem: root}]; 
-    const get_layer = (heading) => {
-        if(heading.matches('h1')) return 1;
-        else if(heading.matches('h2')) return 2;
-        else if(heading.matches('h3')) return 3;
-        else if(heading.matches('h4')) return 4;
-        else if(heading.matches('h5')) return 5;
-        else if(heading.matches('h6')) return 6;
-    }
     let idx = 0;
     for(const heading of headings){
         // observe heading
@@ -102,28 +89,20 @@ async function load_toc_module(){
         })
         heading.dataset.index = idx;
         idx+=1
-        
-        let layer = get_layer(heading); 
-        while(stk[stk.length-1].layer >= layer){
-            stk.pop();
+    }
+    const toc = document.getElementById('toc');
+    for(const entry of toc.querySelectorAll('a[href^="#"]')){
+        const heading = document.getElementById(decodeURIComponent(entry.getAttribute('href').slice(1)));
+        if(!heading){
+            continue;
         }
-        const sub_elem = document.createElement('li');
-        sub_elem.classList.add('pl-4', 'pb-1');
-        const p = document.createElement('p');
-        p.classList.add('text-sm', 'text-gray-900', 'dark:text-gray-200', 'cursor-pointer', 'hover:text-customlight-700', 'dark:hover:text-customdark-400', 'hover:underline');
-        p.innerText = heading.innerText; 
-        p.addEventListener('click', (e)=>{
+        tocDataset.push(entry);
+        entry.addEventListener('click', (e)=>{
+            e.preventDefault();
+            history.replaceState(null, null, entry.getAttribute('href'));
             heading.scrollIntoView({ behavior: "smooth", block: "start", inline: "nearest" });
         })
-        tocDataset.push(p);
-        const ul = document.createElement('ul');
-        sub_elem.appendChild(p);
-        sub_elem.appendChild(ul);
-        stk[stk.length-1].elem.appendChild(sub_elem);
-        stk.push({layer, elem: ul});
     }
-    toc.appendChild(root);
-
 }
 async function load_cache_module(){
     const set_events = async () => {
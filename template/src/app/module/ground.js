@@ -68,5 +68,11 @@ export default class Ground {
     let target_main = target.getElementById("main");
     let target_main_clone = target_main.cloneNode(true);
     main.replaceWith(target_main_clone);
+    // the table of contents is rendered with the page, outside of main
+    let toc = document.getElementById("toc");
+    let target_toc = target.getElementById("toc");
+    if(toc && target_toc){
+      toc.replaceWith(target_toc.cloneNode(true));
+    }
   }
 }
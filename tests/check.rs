@@ -0,0 +1,18 @@
+use std::path::PathBuf;
+
+use helium::{check::Report, context::Strictness};
+
+#[test]
+fn duplicate_keys_fail_at_error_strictness() {
+    let report = Report {
+        duplicates: vec![("README".into(), vec![
+            (PathBuf::from("a/README.md"), PathBuf::from("/post/README.html")),
+            (PathBuf::from("b/README.md"), PathBuf::from("/post/README-1.html")),
+        ])],
+        orphans: vec![PathBuf::from("a/README.md")],
+        ..Default::default()
+    };
+    assert_eq!((report.errors(), report.warnings()), (1, 1));
+    assert!(!report.exceeds(Strictness::None));
+    assert!(report.exceeds(Strictness::Error));
+}
@@ -0,0 +1,167 @@
+#![allow(dead_code)]
+
+use std::{collections::HashSet, fs, path::{Path, PathBuf}, sync::Arc};
+
+use async_trait::async_trait;
+use helium::{build::Build, context::Context, convert::search::SearchIndex, error::HeliumError, util::token::{Token, Tokenizer}, Site};
+use walkdir::WalkDir;
+
+/*
+ * set to write the snapshots of the current output instead of comparing against them
+ */
+static UPDATE_SNAPSHOTS: &str = "HELIUM_UPDATE_SNAPSHOTS";
+
+/*
+ * lowercased alphanumeric words of two characters or more, so the suite needs no python
+ */
+#[derive(Default)]
+pub struct TestTokenizer {
+    stop_words: HashSet<String>,
+}
+
+#[async_trait]
+impl Tokenizer for TestTokenizer {
+    fn id(&self) -> String {
+        "test".into()
+    }
+    fn stop_words(&self) -> &HashSet<String> {
+        &self.stop_words
+    }
+    async fn tokenize(&self, s: &str) -> Result<Token, HeliumError> {
+        let data = s.split(|c: char| !c.is_alphanumeric())
+            .filter(|word| word.chars().count() >= 2)
+            .map(str::to_lowercase)
+            .collect();
+        Ok(Token { data })
+    }
+}
+
+pub struct Fixture {
+    pub site: Site,
+    pub build: Build,
+    /*
+     * target_base and cache of this run, removed when the fixture is dropped
+     */
+    pub scratch: PathBuf,
+}
+impl Fixture {
+    pub fn target(&self) -> &Path {
+        &self.site.context.target_base
+    }
+}
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.scratch);
+    }
+}
+
+pub fn scratch_dir(fixture: &str, test: &str) -> PathBuf {
+    let scratch = std::env::temp_dir().join(format!("helium-test-{}-{}-{}", fixture, test, std::process::id()));
+    let _ = fs::remove_dir_all(&scratch);
+    scratch
+}
+
+/*
+ * load tests/fixtures/<fixture>/config.yaml with the target and cache moved into scratch
+ */
+pub async fn site(fixture: &str, scratch: &Path) -> Site {
+    site_with(fixture, scratch, |_| {}).await
+}
+
+/*
+ * like site, with the config changed before the site is built
+ */
+pub async fn site_with(fixture: &str, scratch: &Path, configure: impl FnOnce(&mut Context)) -> Site {
+    let config = Path::new("tests/fixtures").join(fixture).join("config.yaml");
+    let mut context = Context::new(&config).unwrap_or_else(|err| panic!("{}", err));
+    context.cache_dir = Some(scratch.join("cache"));
+    configure(&mut context);
+
+    Site::builder()
+        .context(context)
+        .target(scratch.join("site"))
+        .tokenizer(Arc::new(TestTokenizer::default()))
+        .build()
+        .await
+        .unwrap_or_else(|err| panic!("{}", err))
+}
+
+/*
+ * run the whole pipeline on a fixture vault
+ */
+pub async fn build(fixture: &str, test: &str) -> Fixture {
+    build_with(fixture, test, |_| {}).await
+}
+
+/*
+ * like build, with the config changed first, every document has to build
+ */
+pub async fn build_with(fixture: &str, test: &str, configure: impl FnOnce(&mut Context)) -> Fixture {
+    let scratch = scratch_dir(fixture, test);
+    let site = site_with(fixture, &scratch, configure).await;
+    let build = site.build().await.unwrap_or_else(|err| panic!("{}", err));
+    assert!(build.failures.is_empty(), "{:?}", build.failures);
+    Fixture { site, build, scratch }
+}
+
+/*
+ * every file of the output tree in path order, text files with their content
+ * and search indices as the documents they list, their filters are checked by the tests instead
+ */
+pub fn render_tree(target: &Path) -> String {
+    let mut tree = String::new();
+    let entries = WalkDir::new(target).min_depth(1).sort_by_file_name();
+    for entry in entries {
+        let entry = entry.unwrap();
+        if entry.file_type().is_dir() {
+            continue;
+        }
+        let rel = entry.path().strip_prefix(target).unwrap();
+        let data = fs::read(entry.path()).unwrap();
+        tree.push_str(&format!("=== {}\n", rel.display()));
+        match (entry.file_name() == "searchindex", String::from_utf8(data.clone())) {
+            (true, _) => {
+                let indices: Vec<SearchIndex> = bincode::deserialize(&data).unwrap();
+                for index in indices {
+                    tree.push_str(&format!("{} {}\n", index.rel, index.title));
+                }
+            },
+            (false, Ok(text)) => {
+                tree.push_str(&text);
+                if !text.ends_with('\n') {
+                    tree.push('\n');
+                }
+            },
+            (false, Err(_)) => tree.push_str(&format!("<{} bytes>\n", data.len())),
+        }
+    }
+    tree
+}
+
+/*
+ * compare against tests/snapshots/<name>.snap, run with HELIUM_UPDATE_SNAPSHOTS=1 to accept a change
+ */
+pub fn assert_snapshot(name: &str, actual: &str) {
+    let path = Path::new("tests/snapshots").join(format!("{}.snap", name));
+    if std::env::var_os(UPDATE_SNAPSHOTS).is_some() {
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|_| panic!("{} is missing, run with {}=1 to create it", path.display(), UPDATE_SNAPSHOTS));
+    if expected == actual {
+        return;
+    }
+
+    let line = expected.lines().zip(actual.lines())
+        .position(|(expected, actual)| expected != actual)
+        .unwrap_or(expected.lines().count().min(actual.lines().count()));
+    panic!(
+        "output differs from {} at line {}\n  expected: {}\n    actual: {}\nrun with {}=1 to accept the new output",
+        path.display(),
+        line + 1,
+        expected.lines().nth(line).unwrap_or("<end of file>"),
+        actual.lines().nth(line).unwrap_or("<end of file>"),
+        UPDATE_SNAPSHOTS,
+    );
+}
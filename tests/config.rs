@@ -0,0 +1,47 @@
+use std::{fs, path::PathBuf};
+
+use helium::context::Context;
+
+mod common;
+
+fn write_config(test: &str, yaml: &str) -> (PathBuf, PathBuf) {
+    let scratch = common::scratch_dir("config", test);
+    fs::create_dir_all(&scratch).unwrap();
+    let path = scratch.join("config.yaml");
+    fs::write(&path, yaml).unwrap();
+    (scratch, path)
+}
+
+#[test]
+fn unknown_field_suggests_the_closest_one() {
+    let (scratch, path) = write_config("unknown", "nodes: []\ntarget_base: out\nopen_file_limit: 64\nrender:\n  templat: x\n");
+    let err = Context::new(&path).unwrap_err().to_string();
+    fs::remove_dir_all(scratch).unwrap();
+
+    assert!(err.contains("unknown field `templat`, did you mean `template`?"), "{}", err);
+    assert!(err.contains("config.yaml:5:3"), "{}", err);
+}
+
+#[test]
+fn validate_lists_every_problem() {
+    let yaml = "nodes:\n  - tests/fixtures/missing\ntarget_base: out\nopen_file_limit: 8\nrender:\n  template: tests/fixtures/templates/*.html\n  collect_documents: true\n  static: []\n  list_size: 0\n";
+    let (scratch, path) = write_config("validate", yaml);
+    let errors: Vec<String> = Context::new(&path).unwrap().validate().iter().map(ToString::to_string).collect();
+    fs::remove_dir_all(scratch).unwrap();
+
+    assert_eq!(errors.len(), 3, "{:?}", errors);
+    assert!(errors[0].contains("node tests/fixtures/missing does not exist") && errors[0].ends_with("config.yaml:2:5"));
+    assert!(errors[1].contains("open_file_limit 8 is out of range") && errors[1].ends_with("config.yaml:4:1"));
+    assert!(errors[2].contains("list_size should be at least 1") && errors[2].ends_with("config.yaml:9:3"));
+}
+
+#[test]
+fn unknown_stop_words_are_reported() {
+    let yaml = "nodes:\n  - tests/fixtures/blog\ntarget_base: out\nopen_file_limit: 64\nrender:\n  template: tests/fixtures/templates/*.html\n  collect_documents: true\n  static: []\n  list_size: 3\ntokenizer:\n  stop_words: [ko, kr]\n";
+    let (scratch, path) = write_config("stop_words", yaml);
+    let errors: Vec<String> = Context::new(&path).unwrap().validate().iter().map(ToString::to_string).collect();
+    fs::remove_dir_all(scratch).unwrap();
+
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert!(errors[0].contains("unknown stop word list `kr`") && errors[0].ends_with("config.yaml:11:3"), "{}", errors[0]);
+}
@@ -0,0 +1,30 @@
+use helium::convert::math::latex_to_mathml;
+
+fn body(latex: &str) -> String {
+    let mathml = latex_to_mathml(latex, false);
+    let start = mathml.find("<semantics><mrow>").unwrap() + "<semantics><mrow>".len();
+    let end = mathml.find("</mrow><annotation").unwrap();
+    mathml[start..end].to_string()
+}
+
+#[test]
+fn formulas_render_to_mathml() {
+    assert_eq!(body("x^2"), "<msup><mi>x</mi><mn>2</mn></msup>");
+    assert_eq!(body(r"\frac{1}{n}"), "<mfrac><mn>1</mn><mi>n</mi></mfrac>");
+    assert_eq!(body(r"\mathbb{R}^n"), "<msup><mi mathvariant=\"double-struck\">R</mi><mi>n</mi></msup>");
+    assert!(latex_to_mathml("a < b", false).contains("<annotation encoding=\"application/x-tex\">a &lt; b</annotation>"));
+}
+
+#[test]
+fn blocks_are_displayed() {
+    assert!(latex_to_mathml(r"\sum_{i=1}^n i", true).contains("<math xmlns=\"http://www.w3.org/1998/Math/MathML\" display=\"block\">"));
+    assert!(!latex_to_mathml(r"\sum_{i=1}^n i", false).contains("display=\"block\""));
+}
+
+#[test]
+fn mistakes_are_shown_in_the_formula() {
+    assert!(latex_to_mathml(r"\foo x", false).contains("<merror><mtext>\\foo x (Undefined control sequence: \\foo)</mtext></merror>"));
+    assert!(latex_to_mathml(r"\frac{1}{2", false).contains("<merror>"));
+    assert!(latex_to_mathml(r"x_1_2", false).contains("(Double subscript)"));
+    assert!(latex_to_mathml(r"x^a^b", true).contains("display=\"block\"><merror><mtext>x^a^b (Double superscript)</mtext>"));
+}
@@ -0,0 +1,166 @@
+use helium::context::HighlightContext;
+use helium::convert::{file::FileType, search::render_search_index};
+use helium::index::{flatten_file_node, NodeType};
+use xorf::Filter;
+
+mod common;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn blog_matches_snapshot() {
+    let fixture = common::build("blog", "snapshot").await;
+    assert!(fixture.build.failures.is_empty());
+    common::assert_snapshot("blog", &common::render_tree(fixture.target()));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn nested_matches_snapshot() {
+    let fixture = common::build("nested", "snapshot").await;
+    assert!(fixture.build.failures.is_empty());
+    common::assert_snapshot("nested", &common::render_tree(fixture.target()));
+}
+
+/*
+ * documents parsed from the cache of the first build render the same site
+ */
+#[tokio::test(flavor = "multi_thread")]
+async fn cached_build_is_identical() {
+    let fixture = common::build("blog", "cached").await;
+    let first = common::render_tree(fixture.target());
+    let build = fixture.site.build().await.unwrap();
+    assert!(build.failures.is_empty());
+    assert_eq!(first, common::render_tree(fixture.target()));
+}
+
+/*
+ * two sites built side by side in one process keep their own output
+ */
+#[tokio::test(flavor = "multi_thread")]
+async fn sites_build_concurrently() {
+    let (blog, nested) = tokio::join!(common::build("blog", "concurrent"), common::build("nested", "concurrent"));
+    assert!(blog.target().join("post/Hello.html").is_file());
+    assert!(!blog.target().join("notes").exists());
+    assert!(nested.target().join("notes/a/3/Note1.html").is_file());
+    assert!(!nested.target().join("post").exists());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn search_index_finds_korean_and_english() {
+    let fixture = common::build("blog", "search").await;
+    let indices = render_search_index(&fixture.site, fixture.build.root.clone()).await.unwrap();
+
+    let find = |word: &str| -> Vec<String> {
+        let mut titles: Vec<String> = indices.iter()
+            .filter(|index| index.filter.contains(&word.to_string()))
+            .map(|index| index.title.clone())
+            .collect();
+        titles.sort();
+        titles
+    };
+    assert_eq!(find("english"), vec!["Hello"]);
+    assert_eq!(find("러스트로"), vec!["안녕"]);
+    assert_eq!(find("borrow"), vec!["Rust"]);
+    assert_eq!(find("ownership"), vec!["Hello", "Rust"]);
+}
+
+/*
+ * %%comments%% reach neither the pages nor the search index, ==highlights== reach both the page and the summary
+ */
+#[tokio::test(flavor = "multi_thread")]
+async fn comments_are_left_out() {
+    let fixture = common::build("blog", "comments").await;
+    let about = std::fs::read_to_string(fixture.target().join("post/About.html")).unwrap();
+    assert!(!about.contains("private") && !about.contains("Drafts"));
+    assert!(!fixture.target().join("tags/secret").exists());
+    assert!(about.contains("<code>%% an indented code block %%\n</code>"));
+    assert!(about.contains("<mark>mostly finished</mark>"));
+    let index = std::fs::read_to_string(fixture.target().join("index.html")).unwrap();
+    assert!(index.contains("The blog is <mark>mostly finished</mark> and a == b stays code."));
+
+    let indices = render_search_index(&fixture.site, fixture.build.root.clone()).await.unwrap();
+    let about = indices.iter().find(|index| index.title == "About").unwrap();
+    assert!(about.filter.contains(&"finished".to_string()));
+    assert!(!about.filter.contains(&"private".to_string()));
+    assert!(!about.filter.contains(&"drafts".to_string()));
+}
+
+/*
+ * tag directories stay at /tags/<tag>/ when the tag listing is paged
+ */
+#[tokio::test(flavor = "multi_thread")]
+async fn tag_chips_point_at_tag_pages() {
+    let fixture = common::build("blog", "tags").await;
+    assert!(fixture.target().join("tags/2/index.html").exists());
+
+    let chip = regex::Regex::new(r#"href="/tags/([^"]+)/""#).unwrap();
+    let mut chips = 0;
+    for entry in std::fs::read_dir(fixture.target().join("post")).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|extension| extension != "html") {
+            continue;
+        }
+        let html = std::fs::read_to_string(path).unwrap();
+        for captures in chip.captures_iter(&html) {
+            let page = fixture.target().join("tags").join(&captures[1]).join("index.html");
+            assert!(page.exists(), "{} does not exist", page.display());
+            chips += 1;
+        }
+    }
+    assert_eq!(chips, 5);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn wikilinks_and_backlinks_resolve() {
+    let fixture = common::build("blog", "links").await;
+    assert!(fixture.build.report.unresolved.is_empty());
+
+    let hello = std::fs::read_to_string(fixture.target().join("post/Hello.html")).unwrap();
+    assert!(hello.contains("href=\"/post/%EC%95%88%EB%85%95.html\""));
+    assert!(hello.contains("href=\"/post/Rust.html#ownership\""));
+    assert!(hello.contains("src=\"/posts/1/images/cat.png\""));
+    for linker in ["About", "안녕", "Rust", "Deep"] {
+        assert!(hello.contains(&format!(">{}</a>", linker)), "{} is not a backlink of Hello", linker);
+    }
+
+    let rust = std::fs::read_to_string(fixture.target().join("post/Rust.html")).unwrap();
+    assert!(rust.contains("<h2 id=\"ownership\">"));
+    assert!(rust.contains(">Option&lt;T&gt; &amp; Result&lt;T, E&gt;</a>"));
+
+    for node in flatten_file_node(&fixture.build.root).await {
+        if let NodeType::File(lk) = &node.property.node_type {
+            if let Some(FileType::Markdown(_, doc)) = &*lk.read().await {
+                assert!(doc.html.read().await.is_some(), "{} was not rendered", doc.path.display());
+            }
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn code_blocks_are_highlighted_at_build_time() {
+    let fixture = common::build_with("blog", "highlight", |context| {
+        context.render.highlight = Some(HighlightContext {
+            theme: "InspiredGitHub".into(),
+            dark_theme: Some("base16-ocean.dark".into()),
+            line_numbers: true,
+        });
+    }).await;
+
+    let rust = std::fs::read_to_string(fixture.target().join("post/Rust.html")).unwrap();
+    assert!(rust.contains("<pre class=\"highlight hl-code\"><code class=\"language-rust\"><span class=\"line-numbers\" aria-hidden=\"true\">1\n2\n</span>"), "{}", rust);
+    assert!(rust.contains("<span class=\"hl-storage hl-type hl-rust\">let</span>"), "{}", rust);
+    assert!(rust.contains("/static/highlight.css"));
+
+    let css = std::fs::read_to_string(fixture.target().join("static/highlight.css")).unwrap();
+    assert!(css.contains(".hl-code {"));
+    assert!(css.contains(".dark .hl-code {"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn sitemap_locations_are_percent_encoded() {
+    let fixture = common::build_with("blog", "sitemap", |context| {
+        context.base_url = Some("https://example.com/".into());
+    }).await;
+
+    let sitemap = std::fs::read_to_string(fixture.target().join("sitemap.xml")).unwrap();
+    assert!(sitemap.contains("<loc>https://example.com/post/%EC%95%88%EB%85%95.html</loc>"), "{}", sitemap);
+    assert!(!sitemap.contains("안녕"));
+}
@@ -0,0 +1,41 @@
+use std::{fs, path::Path};
+
+use helium::{util::watch::Change, watch::rebuild};
+use walkdir::WalkDir;
+
+mod common;
+
+fn copy_vault(from: &Path, to: &Path) {
+    for entry in WalkDir::new(from) {
+        let entry = entry.unwrap();
+        let target = to.join(entry.path().strip_prefix(from).unwrap());
+        match entry.file_type().is_dir() {
+            true => fs::create_dir_all(target).unwrap(),
+            false => {
+                fs::copy(entry.path(), target).unwrap();
+            },
+        }
+    }
+}
+
+/*
+ * a date edit moving notes to other pages changes their urls, the rebuild has to match a full build
+ */
+#[tokio::test(flavor = "multi_thread")]
+async fn rebuild_after_repaging_matches_full_build() {
+    let scratch = common::scratch_dir("nested", "watch");
+    let vault = scratch.join("notes");
+    copy_vault(Path::new("tests/fixtures/nested/notes"), &vault);
+    let site = common::site_with("nested", &scratch, |context| context.nodes = vec![vault.clone()]).await;
+    let mut build = site.build().await.unwrap();
+
+    let note = vault.join("a/Note1.md");
+    fs::write(&note, fs::read_to_string(&note).unwrap().replace("2024-02-01", "2024-03-01")).unwrap();
+    rebuild(&site, &mut build, &[Change::Modified(note)]).await.unwrap();
+    let rebuilt = common::render_tree(&site.context.target_base);
+
+    let full = common::site_with("nested", &scratch.join("full"), |context| context.nodes = vec![vault.clone()]).await;
+    full.build().await.unwrap();
+    assert_eq!(rebuilt, common::render_tree(&full.context.target_base));
+    fs::remove_dir_all(scratch).unwrap();
+}